@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE audit_events (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    country_name VARCHAR(255) NOT NULL,
+    action VARCHAR(20) NOT NULL,
+    source VARCHAR(50) NOT NULL,
+    actor VARCHAR(255),
+    changes JSON NOT NULL,
+    created_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+
+    INDEX idx_audit_country_created (country_name, created_at),
+    INDEX idx_audit_created (created_at)
+);
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE refreshes (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    status VARCHAR(20) NOT NULL,
+    started_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+    completed_at TIMESTAMP(3) NULL,
+    error TEXT,
+    diff JSON,
+
+    INDEX idx_refresh_started (started_at)
+);
@@ -0,0 +1,25 @@
+-- Add migration script here
+CREATE TABLE country_history (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    country_id INT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    capital VARCHAR(255),
+    region VARCHAR(100),
+    population BIGINT NOT NULL,
+    currency_code VARCHAR(10),
+    exchange_rate DECIMAL(20, 8),
+    estimated_gdp DECIMAL(30, 2),
+    flag_url TEXT,
+    last_refreshed_at TIMESTAMP NOT NULL,
+    valid_from TIMESTAMP(3) NOT NULL,
+    valid_to TIMESTAMP(3) NULL,
+
+    INDEX idx_history_name_valid (name, valid_from, valid_to),
+    INDEX idx_history_valid (valid_from, valid_to)
+);
+
+INSERT INTO country_history (country_id, name, capital, region, population, currency_code,
+    exchange_rate, estimated_gdp, flag_url, last_refreshed_at, valid_from)
+SELECT id, name, capital, region, population, currency_code,
+    exchange_rate, estimated_gdp, flag_url, last_refreshed_at, last_refreshed_at
+FROM countries;
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE countries ADD COLUMN rate_provider VARCHAR(50) AFTER exchange_rate;
+
+ALTER TABLE country_history ADD COLUMN rate_provider VARCHAR(50) AFTER exchange_rate;
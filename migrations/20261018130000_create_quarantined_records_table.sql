@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE quarantined_records (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    refresh_id BIGINT NOT NULL,
+    source VARCHAR(50) NOT NULL,
+    country_name VARCHAR(255),
+    reason TEXT NOT NULL,
+    payload JSON NOT NULL,
+    created_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+
+    INDEX idx_quarantined_refresh (refresh_id)
+);
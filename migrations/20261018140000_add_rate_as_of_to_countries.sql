@@ -0,0 +1,9 @@
+-- Add migration script here
+ALTER TABLE countries ADD COLUMN rate_as_of TIMESTAMP(3) NULL AFTER rate_provider;
+
+ALTER TABLE country_history ADD COLUMN rate_as_of TIMESTAMP(3) NULL AFTER rate_provider;
+
+-- Rates stored before this column existed are at least as old as their refresh
+UPDATE countries SET rate_as_of = last_refreshed_at WHERE exchange_rate IS NOT NULL;
+
+UPDATE country_history SET rate_as_of = last_refreshed_at WHERE exchange_rate IS NOT NULL;
@@ -0,0 +1,25 @@
+-- Add migration script here
+CREATE TABLE rate_overrides (
+    currency_code VARCHAR(10) PRIMARY KEY,
+    rate DECIMAL(20, 8) NOT NULL,
+    reason TEXT NOT NULL,
+    expires_at TIMESTAMP(3) NOT NULL,
+    created_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+    updated_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3) ON UPDATE CURRENT_TIMESTAMP(3)
+);
+
+ALTER TABLE countries
+    ADD COLUMN rate_source VARCHAR(10) AFTER rate_as_of,
+    ADD COLUMN feed_exchange_rate DECIMAL(20, 8) AFTER rate_source;
+
+ALTER TABLE country_history
+    ADD COLUMN rate_source VARCHAR(10) AFTER rate_as_of,
+    ADD COLUMN feed_exchange_rate DECIMAL(20, 8) AFTER rate_source;
+
+UPDATE countries
+SET rate_source = 'feed', feed_exchange_rate = exchange_rate
+WHERE exchange_rate IS NOT NULL;
+
+UPDATE country_history
+SET rate_source = 'feed', feed_exchange_rate = exchange_rate
+WHERE exchange_rate IS NOT NULL;
@@ -0,0 +1,10 @@
+-- Add migration script here
+ALTER TABLE countries
+    ADD COLUMN alpha2_code CHAR(2) NULL AFTER name,
+    ADD COLUMN alpha3_code CHAR(3) NULL AFTER alpha2_code,
+    ADD INDEX idx_alpha2_code (alpha2_code),
+    ADD INDEX idx_alpha3_code (alpha3_code);
+
+ALTER TABLE country_history
+    ADD COLUMN alpha2_code CHAR(2) NULL AFTER name,
+    ADD COLUMN alpha3_code CHAR(3) NULL AFTER alpha2_code;
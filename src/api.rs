@@ -1,15 +1,57 @@
+use std::time::Duration;
+
 use axum::{
     Router,
-    routing::{delete, get, post},
+    extract::DefaultBodyLimit,
+    http::StatusCode,
+    middleware::{from_fn_with_state, map_response, map_response_with_state},
+    routing::{delete, get, post, put},
+};
+use tower_http::{
+    limit::RequestBodyLimitLayer,
+    request_id::{PropagateRequestIdLayer, SetRequestIdLayer},
+    timeout::TimeoutLayer,
+};
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
 };
-use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
-    models::{country::Country, requests::CountryFilters, responses::ApiError, state::AppState},
-    routes::countries::{
-        delete_country, get_countries, get_country, get_status, get_summary_image,
-        refresh_countries,
+    auth::{API_KEY_HEADER, require_scope},
+    middleware::{
+        MakeHexRequestId, REQUEST_ID_HEADER, compression_layer, cors_layer, layer_errors,
+        limit_auth_failures, rate_limit, request_context, weaken_encoded_etag,
+    },
+    models::{
+        audit::AuditEvent,
+        country::Country,
+        quarantine::QuarantinedRecord,
+        rate_override::{RateOverride, RateOverrideRequest},
+        refresh::{
+            CurrencyChange, PopulationChange, PreviewGroup, RateChange, RefreshDiff, RefreshPreview,
+        },
+        requests::{
+            AsOfQuery, AuditFilters, BatchCountriesRequest, CountryFilters, CountryQuery,
+            RefreshQuery, RefreshRequest, RefreshScope,
+        },
+        responses::{ApiError, BatchCountriesResponse, ProblemDetails},
+        scope::Scope,
+        state::AppState,
+    },
+    routes::{
+        audit::get_audit_events,
+        countries::{
+            delete_country, get_countries, get_countries_batch, get_country, get_status,
+            get_summary_image, refresh_countries,
+        },
+        currencies::{delete_rate_override, put_rate_override},
+        refreshes::{get_refresh_diff, get_refresh_quarantine},
+    },
+    utils::{
+        rate_limit::RateBudget,
+        resilience::{CircuitState, UpstreamStatus},
     },
 };
 
@@ -19,20 +61,52 @@ use crate::{
         crate::routes::countries::refresh_countries,
         crate::routes::countries::get_countries,
         crate::routes::countries::get_country,
+        crate::routes::countries::get_countries_batch,
         crate::routes::countries::delete_country,
         crate::routes::countries::get_status,
         crate::routes::countries::get_summary_image,
+        crate::routes::currencies::put_rate_override,
+        crate::routes::currencies::delete_rate_override,
+        crate::routes::audit::get_audit_events,
+        crate::routes::refreshes::get_refresh_diff,
+        crate::routes::refreshes::get_refresh_quarantine,
     ),
     components(
         schemas(
             CountryFilters,
+            CountryQuery,
+            AsOfQuery,
+            RefreshQuery,
+            RefreshRequest,
+            RefreshScope,
+            BatchCountriesRequest,
+            BatchCountriesResponse,
+            AuditFilters,
             ApiError,
+            ProblemDetails,
             Country,
+            AuditEvent,
+            RefreshDiff,
+            PopulationChange,
+            CurrencyChange,
+            RateChange,
+            RefreshPreview,
+            PreviewGroup,
+            QuarantinedRecord,
+            RateOverride,
+            RateOverrideRequest,
+            UpstreamStatus,
+            CircuitState,
         )
     ),
     tags(
-        (name = "Countries", description = "Country Currency & Exchange API endpoints")
+        (name = "Countries", description = "Country Currency & Exchange API endpoints"),
+        (name = "Currencies", description = "Manual exchange rate overrides"),
+        (name = "Audit", description = "History of changes made to country data"),
+        (name = "Refreshes", description = "Results of country data refreshes")
     ),
+    modifiers(&SecurityAddon),
+    security(("api_key" = []), ("bearer" = [])),
     info(
         title = "Country Currency & Exchange API",
         version = "1.0.0",
@@ -42,13 +116,108 @@ use crate::{
 pub struct ApiDoc;
 
 pub fn build_router(state: AppState) -> Router {
-    Router::new()
-        .route("/countries/refresh", post(refresh_countries))
+    let config = state.config.clone();
+    let require = |scope: Scope| from_fn_with_state((state.clone(), scope), require_scope);
+    let limit = |budget: RateBudget| from_fn_with_state((state.clone(), budget), rate_limit);
+    // Refreshes are left out: retrying every rate provider can outlast any
+    // sensible request timeout, and they return before their background work
+    let timeout = |router: Router<AppState>| match config.request_timeout_secs {
+        0 => router,
+        secs => router.route_layer(TimeoutLayer::with_status_code(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Duration::from_secs(secs),
+        )),
+    };
+
+    // Route layers added later run first, so callers are authenticated
+    // before their rate limit is looked up. Failed authentications are
+    // limited by address in a layer around all of them.
+
+    let reads = Router::new()
         .route("/countries", get(get_countries))
         .route("/countries/{name}", get(get_country))
-        .route("/countries/{name}", delete(delete_country))
+        .route("/countries/batch", post(get_countries_batch))
         .route("/status", get(get_status))
         .route("/countries/image", get(get_summary_image))
+        .route("/audit", get(get_audit_events))
+        .route("/refreshes/{id}/diff", get(get_refresh_diff))
+        .route("/refreshes/{id}/quarantine", get(get_refresh_quarantine))
+        .route_layer(limit(RateBudget::Read))
+        .route_layer(require(Scope::CountriesRead));
+
+    let refreshes = Router::new()
+        .route("/countries/refresh", post(refresh_countries))
+        .route_layer(limit(RateBudget::Refresh))
+        .route_layer(require(Scope::CountriesRefresh));
+
+    let deletes = Router::new()
+        .route("/countries/{name}", delete(delete_country))
+        .route_layer(require(Scope::CountriesWrite));
+
+    let overrides = Router::new()
+        .route(
+            "/currencies/{code}/override",
+            put(put_rate_override).delete(delete_rate_override),
+        )
+        .route_layer(require(Scope::RatesWrite));
+
+    let mut router = Router::new()
+        .merge(timeout(reads))
+        .merge(refreshes)
+        .merge(timeout(deletes))
+        .merge(timeout(overrides))
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .layer(from_fn_with_state(state.clone(), limit_auth_failures))
         .with_state(state)
+        // Chunked bodies are capped as they are read, not just by Content-Length
+        .layer(RequestBodyLimitLayer::new(config.max_body_bytes))
+        .layer(DefaultBodyLimit::disable())
+        .layer(map_response_with_state(config.max_body_bytes, layer_errors));
+
+    // Layers added later wrap the ones before, so every response, including
+    // CORS preflights, gets a request ID
+    if let Some(compression) = compression_layer(&config) {
+        router = router
+            .layer(compression)
+            .layer(map_response(weaken_encoded_etag));
+    }
+    if let Some(cors) = cors_layer(&config) {
+        router = router.layer(cors);
+    }
+
+    router
+        .layer(from_fn_with_state(config.error_format, request_context))
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeHexRequestId))
+}
+
+/// Adds the `X-API-Key` and bearer schemes referenced by `security` on `ApiDoc`.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                API_KEY_HEADER.as_str(),
+                "Required when AUTH_ENABLED is set. Read endpoints need the reader role; \
+                 refreshes, deletes and rate overrides need admin",
+            ))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some(
+                        "Accepted when JWKS_FILE or JWKS_URL is set. Reads need \
+                         countries:read, refreshes countries:refresh, deletes \
+                         countries:write and rate overrides rates:write",
+                    ))
+                    .build(),
+            ),
+        );
+    }
 }
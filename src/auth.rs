@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, header::AUTHORIZATION},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::AppError,
+    jwt::claim_scopes,
+    models::{scope::Scope, state::AppState},
+};
+
+pub const API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+const API_KEY_PREFIX: &str = "cxa_";
+
+/// Who made a request, added to the request extensions once authenticated.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// The API key's name or the token's `sub`, recorded as the audit `actor`
+    pub subject: String,
+    /// Unique per caller, unlike `subject`: `api_key:<id>` or
+    /// `jwt:<iss>:<sub>`. Rate limits are keyed by it.
+    pub id: String,
+    pub scopes: Vec<Scope>,
+    /// Every verified claim, for bearer token callers
+    pub claims: Option<Map<String, Value>>,
+}
+
+impl Principal {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+/// A new random API key and the prefix and hash to store for it. The key
+/// itself is only ever shown once, when created.
+pub struct GeneratedApiKey {
+    pub key: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+pub fn generate_api_key() -> GeneratedApiKey {
+    let key = format!(
+        "{}{}",
+        API_KEY_PREFIX,
+        hex::encode(rand::random::<[u8; 32]>())
+    );
+
+    GeneratedApiKey {
+        prefix: key[..API_KEY_PREFIX.len() + 8].to_string(),
+        hash: hash_api_key(&key),
+        key,
+    }
+}
+
+/// SHA-256, hex encoded. Keys are 256 random bits, so a slow password hash
+/// would add nothing.
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Rejects requests without an API key or bearer token granting `required`
+/// when `AUTH_ENABLED` is set, and records the caller as a `Principal`.
+pub async fn require_scope(
+    State((state, required)): State<(AppState, Scope)>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !state.config.auth_enabled {
+        return Ok(next.run(request).await);
+    }
+
+    let principal = authenticate(&state, request.headers()).await?;
+
+    if !principal.has_scope(required) {
+        return Err(AppError::Forbidden(format!(
+            "Requires the {} scope",
+            required
+        )));
+    }
+
+    request.extensions_mut().insert(principal);
+
+    Ok(next.run(request).await)
+}
+
+async fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<Principal, AppError> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if let Some(token) = bearer {
+        let verifier = state
+            .jwt
+            .as_ref()
+            .ok_or_else(|| AppError::Unauthorized("Bearer tokens are not accepted".to_string()))?;
+
+        let claims = verifier
+            .verify(token.trim())
+            .await
+            .map_err(|e| AppError::Unauthorized(format!("Invalid bearer token: {}", e)))?;
+
+        let subject = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or_else(|| AppError::Unauthorized("Invalid bearer token: no subject".to_string()))?
+            .to_string();
+
+        let issuer = claims.get("iss").and_then(Value::as_str).unwrap_or("");
+
+        return Ok(Principal {
+            id: format!("jwt:{}:{}", issuer, subject),
+            subject,
+            scopes: claim_scopes(&claims),
+            claims: Some(claims),
+        });
+    }
+
+    let key = headers
+        .get(&API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("Missing API key".to_string()))?;
+
+    let api_key = state
+        .api_keys
+        .authenticate(&hash_api_key(key))
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()))?;
+
+    Ok(Principal {
+        id: format!("api_key:{}", api_key.id),
+        subject: api_key.name,
+        scopes: api_key.role.scopes().to_vec(),
+        claims: None,
+    })
+}
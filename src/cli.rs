@@ -0,0 +1,66 @@
+use anyhow::{Result, anyhow};
+
+use crate::{auth::generate_api_key, db::repositories::ApiKeyRepository, models::api_key::Role};
+
+const API_KEYS_USAGE: &str = "Usage:
+  api-keys create <name> [--role reader|admin]
+  api-keys list
+  api-keys revoke <id>";
+
+/// Runs `currency_exchange_api api-keys ...`.
+pub async fn api_keys_command(args: &[String], repository: &ApiKeyRepository) -> Result<()> {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    match args.as_slice() {
+        ["create", name, rest @ ..] => {
+            let role = match rest {
+                [] => Role::Reader,
+                ["--role", role] => role.parse()?,
+                _ => return Err(anyhow!(API_KEYS_USAGE)),
+            };
+
+            let generated = generate_api_key();
+            let api_key = repository
+                .create(name, role, &generated.prefix, &generated.hash)
+                .await?;
+
+            println!(
+                "Created {} key {} ({})",
+                api_key.role, api_key.id, api_key.name
+            );
+            println!("{}", generated.key);
+            println!("Store it now: it cannot be shown again.");
+        }
+        ["list"] => {
+            for api_key in repository.list().await? {
+                println!(
+                    "{}\t{}\t{}\t{}...\tcreated {}\tlast used {}{}",
+                    api_key.id,
+                    api_key.name,
+                    api_key.role,
+                    api_key.key_prefix,
+                    api_key.created_at,
+                    api_key.last_used_at.as_deref().unwrap_or("never"),
+                    api_key
+                        .revoked_at
+                        .map(|at| format!("\trevoked {}", at))
+                        .unwrap_or_default(),
+                );
+            }
+        }
+        ["revoke", id] => {
+            let id = id
+                .parse::<i64>()
+                .map_err(|_| anyhow!("Invalid key id {:?}", id))?;
+
+            if repository.revoke(id).await? {
+                println!("Revoked key {}", id);
+            } else {
+                return Err(anyhow!("No active key with id {}", id));
+            }
+        }
+        _ => return Err(anyhow!(API_KEYS_USAGE)),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,223 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::{country::Country, rate_override::ActiveOverrides, requests::CountryFilters};
+
+/// Distinct filter combinations kept at once. Past this the query results
+/// are dropped and rebuilt on demand; the snapshot is kept.
+const MAX_CACHED_QUERIES: usize = 1024;
+
+/// Counters since startup, reported by `GET /status`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+    /// Filter combinations currently cached, besides the snapshot
+    pub cached_queries: usize,
+}
+
+/// The parts of `CountryFilters` that shape a current (not `as_of`) result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryKey {
+    region: Option<String>,
+    currency: Option<String>,
+    ascending: bool,
+}
+
+impl QueryKey {
+    pub fn new(filters: &CountryFilters) -> Self {
+        Self {
+            region: filters.region.as_deref().map(str::to_lowercase),
+            currency: filters.currency.as_deref().map(str::to_lowercase),
+            ascending: filters.sort.as_deref() == Some("gdp_asc"),
+        }
+    }
+
+    /// Whether this is the whole table in the default order, i.e. the snapshot.
+    pub fn is_unfiltered(&self) -> bool {
+        self.region.is_none() && self.currency.is_none() && !self.ascending
+    }
+}
+
+struct Entry {
+    countries: Arc<Vec<Country>>,
+    cached_at: Instant,
+}
+
+struct OverridesEntry {
+    overrides: Arc<ActiveOverrides>,
+    cached_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Bumped by every invalidation, so a load that raced a write isn't stored
+    generation: u64,
+    snapshot: Option<Entry>,
+    queries: HashMap<QueryKey, Entry>,
+    overrides: Option<OverridesEntry>,
+}
+
+/// In-memory copies of current country reads: the whole table, the result
+/// of each filter combination and the active rate overrides. Writes through
+/// the repositories clear it, and entries expire after `ttl` so writes by
+/// other instances show up.
+#[derive(Clone)]
+pub struct CountryCache {
+    ttl: Duration,
+    state: Arc<Mutex<CacheState>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    invalidations: Arc<AtomicU64>,
+}
+
+impl CountryCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            state: Arc::new(Mutex::new(CacheState::default())),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+            invalidations: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The cached full table, if fresh.
+    pub fn snapshot(&self) -> Option<Arc<Vec<Country>>> {
+        let state = self.state.lock().unwrap();
+        self.record(self.fresh(state.snapshot.as_ref()))
+    }
+
+    /// The cached result for `key`, if fresh.
+    pub fn query(&self, key: &QueryKey) -> Option<Arc<Vec<Country>>> {
+        let state = self.state.lock().unwrap();
+        self.record(self.fresh(state.queries.get(key)))
+    }
+
+    /// Read before loading from the database, and passed back when storing.
+    pub fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    pub fn store_snapshot(&self, generation: u64, countries: Vec<Country>) -> Arc<Vec<Country>> {
+        let countries = Arc::new(countries);
+        let mut state = self.state.lock().unwrap();
+
+        if state.generation == generation {
+            state.snapshot = Some(Entry {
+                countries: countries.clone(),
+                cached_at: Instant::now(),
+            });
+        }
+
+        countries
+    }
+
+    pub fn store_query(
+        &self,
+        generation: u64,
+        key: QueryKey,
+        countries: Vec<Country>,
+    ) -> Arc<Vec<Country>> {
+        let countries = Arc::new(countries);
+        let mut state = self.state.lock().unwrap();
+
+        if state.generation == generation {
+            if state.queries.len() >= MAX_CACHED_QUERIES {
+                state.queries.clear();
+            }
+            state.queries.insert(
+                key,
+                Entry {
+                    countries: countries.clone(),
+                    cached_at: Instant::now(),
+                },
+            );
+        }
+
+        countries
+    }
+
+    /// The cached active overrides, if fresh and none of them has expired by
+    /// `now`.
+    pub fn overrides(&self, now: DateTime<Utc>) -> Option<Arc<ActiveOverrides>> {
+        let state = self.state.lock().unwrap();
+        let overrides = state
+            .overrides
+            .as_ref()
+            .filter(|entry| entry.cached_at.elapsed() < self.ttl)
+            .filter(|entry| {
+                entry
+                    .overrides
+                    .next_expiry()
+                    .is_none_or(|expires_at| now < expires_at)
+            })
+            .map(|entry| entry.overrides.clone());
+
+        self.record(overrides)
+    }
+
+    pub fn store_overrides(
+        &self,
+        generation: u64,
+        overrides: ActiveOverrides,
+    ) -> Arc<ActiveOverrides> {
+        let overrides = Arc::new(overrides);
+        let mut state = self.state.lock().unwrap();
+
+        if state.generation == generation {
+            state.overrides = Some(OverridesEntry {
+                overrides: overrides.clone(),
+                cached_at: Instant::now(),
+            });
+        }
+
+        overrides
+    }
+
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.snapshot = None;
+        state.queries.clear();
+        state.overrides = None;
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            cached_queries: self.state.lock().unwrap().queries.len(),
+        }
+    }
+
+    fn fresh(&self, entry: Option<&Entry>) -> Option<Arc<Vec<Country>>> {
+        entry
+            .filter(|entry| entry.cached_at.elapsed() < self.ttl)
+            .map(|entry| entry.countries.clone())
+    }
+
+    fn record<T>(&self, cached: Option<T>) -> Option<T> {
+        let counter = if cached.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        cached
+    }
+}
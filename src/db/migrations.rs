@@ -0,0 +1,55 @@
+use anyhow::{Result, anyhow};
+use sqlx::migrate::Migrator;
+
+use crate::db::pool::DbPool;
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Applies any pending embedded migrations.
+pub async fn run_migrations(pool: &DbPool) -> Result<()> {
+    MIGRATOR
+        .run(pool)
+        .await
+        .map_err(|e| anyhow!("Failed to run migrations: {}", e))
+}
+
+/// Checks the applied migrations against the ones embedded in this binary and
+/// returns the latest applied version. Fails if a migration was left dirty or
+/// the database has a migration this binary does not know about.
+pub async fn verify_schema(pool: &DbPool) -> Result<Option<i64>> {
+    let (table_exists,) = sqlx::query_as::<_, (i64,)>(
+        "SELECT COUNT(*) FROM information_schema.tables
+         WHERE table_schema = DATABASE() AND table_name = '_sqlx_migrations'",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if table_exists == 0 {
+        return Ok(None);
+    }
+
+    let applied = sqlx::query_as::<_, (i64, bool)>(
+        "SELECT version, success FROM _sqlx_migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if let Some((version, _)) = applied.iter().find(|(_, success)| !success) {
+        return Err(anyhow!(
+            "Database schema is dirty: migration {} did not complete",
+            version
+        ));
+    }
+
+    if let Some((version, _)) = applied
+        .iter()
+        .find(|(version, _)| !MIGRATOR.iter().any(|m| m.version == *version))
+    {
+        return Err(anyhow!(
+            "Database schema is newer than this binary: unknown migration {}",
+            version
+        ));
+    }
+
+    Ok(applied.last().map(|(version, _)| *version))
+}
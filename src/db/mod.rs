@@ -1,2 +1,4 @@
+pub mod cache;
+pub mod migrations;
 pub mod pool;
 pub mod repositories;
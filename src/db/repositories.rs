@@ -1,20 +1,178 @@
-use bigdecimal::{BigDecimal, ToPrimitive};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use bigdecimal::BigDecimal;
 use chrono::{DateTime, SecondsFormat, Utc};
-use sqlx::{QueryBuilder, query};
+use serde_json::Value;
+use sqlx::{
+    Decode, MySql, MySqlConnection, QueryBuilder, Row, Type, mysql::MySqlRow, query, types::Json,
+};
 
 use crate::{
-    db::pool::DbPool,
-    models::{country::Country, requests::CountryFilters},
+    db::{
+        cache::{CacheStats, CountryCache, QueryKey},
+        pool::DbPool,
+    },
+    models::{
+        api_key::{ApiKey, Role},
+        audit::{AuditEvent, NewAuditEvent},
+        country::{Country, CountryFields},
+        quarantine::{NewQuarantinedRecord, QuarantinedRecord},
+        rate_override::{ActiveOverrides, RateOverride},
+        refresh::{Refresh, RefreshDiff, RefreshStatus},
+        requests::CountryFilters,
+    },
 };
 
+/// Every stored `countries` column, as `country_history` has them too.
+const COUNTRY_COLUMNS: [&str; 16] = [
+    "id",
+    "name",
+    "alpha2_code",
+    "alpha3_code",
+    "capital",
+    "region",
+    "population",
+    "currency_code",
+    "exchange_rate",
+    "rate_provider",
+    "rate_as_of",
+    "rate_source",
+    "feed_exchange_rate",
+    "estimated_gdp",
+    "flag_url",
+    "last_refreshed_at",
+];
+
+/// `name` from `row` if it was selected, else the type's default.
+fn column<'r, T>(row: &'r MySqlRow, columns: &[&str], name: &str) -> Result<T, sqlx::Error>
+where
+    T: Decode<'r, MySql> + Type<MySql> + Default,
+{
+    if columns.contains(&name) {
+        row.try_get(name)
+    } else {
+        Ok(T::default())
+    }
+}
+
+fn country_from_row(row: &MySqlRow, columns: &[&str]) -> Result<Country, sqlx::Error> {
+    let timestamp = |value: Option<DateTime<Utc>>| {
+        value.map(|ts| ts.to_rfc3339_opts(SecondsFormat::Millis, true))
+    };
+
+    Ok(Country {
+        id: column(row, columns, "id")?,
+        name: column(row, columns, "name")?,
+        alpha2_code: column(row, columns, "alpha2_code")?,
+        alpha3_code: column(row, columns, "alpha3_code")?,
+        capital: column(row, columns, "capital")?,
+        region: column(row, columns, "region")?,
+        population: column(row, columns, "population")?,
+        currency_code: column(row, columns, "currency_code")?,
+        exchange_rate: column(row, columns, "exchange_rate")?,
+        rate_provider: column(row, columns, "rate_provider")?,
+        rate_as_of: timestamp(column(row, columns, "rate_as_of")?),
+        is_stale: false,
+        rate_source: column(row, columns, "rate_source")?,
+        feed_exchange_rate: column(row, columns, "feed_exchange_rate")?,
+        estimated_gdp: column(row, columns, "estimated_gdp")?,
+        flag_url: column(row, columns, "flag_url")?,
+        last_refreshed_at: timestamp(column(row, columns, "last_refreshed_at")?)
+            .unwrap_or_default(),
+    })
+}
+
+/// Pushes a `SELECT` of `columns` from either table: `countries`, or the
+/// `country_history` rows that were current at `as_of`, so filters can
+/// follow with `AND`.
+fn push_country_source(
+    query: &mut QueryBuilder<'_, MySql>,
+    columns: &[&str],
+    as_of: Option<DateTime<Utc>>,
+) {
+    let mut select = query.push("SELECT ").separated(", ");
+    for column in columns {
+        match (*column, as_of) {
+            ("id", Some(_)) => select.push("country_id AS id"),
+            (column, _) => select.push(column),
+        };
+    }
+
+    match as_of {
+        Some(as_of) => {
+            query.push(" FROM country_history WHERE valid_from <= ");
+            query.push_bind(as_of);
+            query.push(" AND (valid_to IS NULL OR valid_to > ");
+            query.push_bind(as_of);
+            query.push(")");
+        }
+        None => {
+            query.push(" FROM countries WHERE 1=1");
+        }
+    }
+}
+
+async fn fetch_countries(
+    pool: &DbPool,
+    mut query: QueryBuilder<'_, MySql>,
+    columns: &[&str],
+) -> Result<Vec<Country>, sqlx::Error> {
+    query
+        .build()
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| country_from_row(row, columns))
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct CountryRepository {
     pool: DbPool,
+    cache: Option<CountryCache>,
 }
 
 impl CountryRepository {
     pub fn new(pool: DbPool) -> Self {
-        Self { pool }
+        Self { pool, cache: None }
+    }
+
+    /// Serves current reads from memory for up to `ttl`, clearing the cache
+    /// whenever countries are written through this repository.
+    pub fn with_cache(pool: DbPool, ttl: Duration) -> Self {
+        Self {
+            pool,
+            cache: Some(CountryCache::new(ttl)),
+        }
+    }
+
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(CountryCache::stats)
+    }
+
+    /// The read cache, to share with `RateOverrideRepository::with_cache`.
+    pub fn cache(&self) -> Option<CountryCache> {
+        self.cache.clone()
+    }
+
+    fn invalidate_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate();
+        }
+    }
+
+    /// The whole table, from the cache when there is one.
+    async fn snapshot(&self, cache: &CountryCache) -> Result<Arc<Vec<Country>>, sqlx::Error> {
+        if let Some(countries) = cache.snapshot() {
+            return Ok(countries);
+        }
+
+        let generation = cache.generation();
+        let countries = self
+            .query(&CountryFilters::default(), None, &COUNTRY_COLUMNS)
+            .await?;
+
+        Ok(cache.store_snapshot(generation, countries))
     }
 
     pub async fn insert_or_update(&self, countries: &[Country]) -> Result<usize, sqlx::Error> {
@@ -24,21 +182,35 @@ impl CountryRepository {
 
         const BATCH_SIZE: usize = 100;
         let mut total_saved = 0;
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
 
         for chunk in countries.chunks(BATCH_SIZE) {
             let mut query_builder = QueryBuilder::new(
-                "INSERT INTO countries (id, name, capital, region, population, currency_code, 
-                exchange_rate, estimated_gdp, flag_url, last_refreshed_at)",
+                "INSERT INTO countries (id, name, alpha2_code, alpha3_code, capital, region,
+                population, currency_code, exchange_rate, rate_provider, rate_as_of, rate_source, feed_exchange_rate,
+                estimated_gdp, flag_url, last_refreshed_at)",
             );
 
             query_builder.push_values(chunk, |mut b, country| {
                 b.push_bind(country.id)
                     .push_bind(&country.name)
+                    .push_bind(&country.alpha2_code)
+                    .push_bind(&country.alpha3_code)
                     .push_bind(&country.capital)
                     .push_bind(&country.region)
                     .push_bind(country.population)
                     .push_bind(&country.currency_code)
                     .push_bind(&country.exchange_rate)
+                    .push_bind(&country.rate_provider)
+                    .push_bind(
+                        country
+                            .rate_as_of
+                            .as_ref()
+                            .and_then(|ts| ts.parse::<DateTime<Utc>>().ok()),
+                    )
+                    .push_bind(&country.rate_source)
+                    .push_bind(&country.feed_exchange_rate)
                     .push_bind(&country.estimated_gdp)
                     .push_bind(&country.flag_url)
                     .push_bind(country.last_refreshed_at.parse::<DateTime<Utc>>().unwrap());
@@ -46,29 +218,108 @@ impl CountryRepository {
 
             query_builder.push(
                 " ON DUPLICATE KEY UPDATE
+                        alpha2_code = VALUES(alpha2_code),
+                        alpha3_code = VALUES(alpha3_code),
                         capital = VALUES(capital),
                         region = VALUES(region),
                         population = VALUES(population),
                         currency_code = VALUES(currency_code),
                         exchange_rate = VALUES(exchange_rate),
+                        rate_provider = VALUES(rate_provider),
+                        rate_as_of = VALUES(rate_as_of),
+                        rate_source = VALUES(rate_source),
+                        feed_exchange_rate = VALUES(feed_exchange_rate),
                         estimated_gdp = VALUES(estimated_gdp),
                         flag_url = VALUES(flag_url),
                         last_refreshed_at = VALUES(last_refreshed_at)",
             );
 
-            let result = query_builder.build().execute(&self.pool).await?;
+            let result = query_builder.build().execute(&mut *tx).await?;
             total_saved += result.rows_affected() as usize;
+
+            // Close the current versions and snapshot the rows as they are now stored
+            let mut close_query = QueryBuilder::new("UPDATE country_history SET valid_to = ");
+            close_query.push_bind(now);
+            close_query.push(" WHERE valid_to IS NULL AND name IN (");
+            let mut names = close_query.separated(", ");
+            for country in chunk {
+                names.push_bind(&country.name);
+            }
+            close_query.push(")");
+            close_query.build().execute(&mut *tx).await?;
+
+            let mut snapshot_query = QueryBuilder::new(
+                "INSERT INTO country_history (country_id, name, alpha2_code, alpha3_code, capital,
+                region, population, currency_code, exchange_rate, rate_provider, rate_as_of,
+                rate_source, feed_exchange_rate, estimated_gdp, flag_url, last_refreshed_at,
+                valid_from)
+                SELECT id, name, alpha2_code, alpha3_code, capital, region, population, currency_code,
+                exchange_rate, rate_provider, rate_as_of, rate_source, feed_exchange_rate,
+                estimated_gdp, flag_url, last_refreshed_at, ",
+            );
+            snapshot_query.push_bind(now);
+            snapshot_query.push(" FROM countries WHERE name IN (");
+            let mut names = snapshot_query.separated(", ");
+            for country in chunk {
+                names.push_bind(&country.name);
+            }
+            snapshot_query.push(")");
+            snapshot_query.build().execute(&mut *tx).await?;
         }
 
+        tx.commit().await?;
+        self.invalidate_cache();
+
         Ok(total_saved)
     }
 
-    pub async fn filter(&self, filters: &CountryFilters) -> Result<Vec<Country>, sqlx::Error> {
-        let mut query = QueryBuilder::new(
-            "SELECT id, name, capital, region, population, currency_code, 
-                exchange_rate, estimated_gdp, flag_url, last_refreshed_at 
-         FROM countries WHERE 1=1",
-        );
+    pub async fn filter(
+        &self,
+        filters: &CountryFilters,
+        as_of: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Country>, sqlx::Error> {
+        let Some(cache) = self.cache.as_ref().filter(|_| as_of.is_none()) else {
+            return self.query(filters, as_of, &COUNTRY_COLUMNS).await;
+        };
+
+        let key = QueryKey::new(filters);
+        if key.is_unfiltered() {
+            return Ok(self.snapshot(cache).await?.to_vec());
+        }
+
+        if let Some(countries) = cache.query(&key) {
+            return Ok(countries.to_vec());
+        }
+
+        let generation = cache.generation();
+        let countries = self.query(filters, None, &COUNTRY_COLUMNS).await?;
+
+        Ok(cache.store_query(generation, key, countries).to_vec())
+    }
+
+    /// Like `filter`, but only reads the columns `fields` needs. Cached
+    /// reads already hold whole rows, so those still come from memory.
+    pub async fn filter_fields(
+        &self,
+        filters: &CountryFilters,
+        as_of: Option<DateTime<Utc>>,
+        fields: &CountryFields,
+    ) -> Result<Vec<Country>, sqlx::Error> {
+        if fields.is_all() || (self.cache.is_some() && as_of.is_none()) {
+            return self.filter(filters, as_of).await;
+        }
+
+        self.query(filters, as_of, &fields.columns()).await
+    }
+
+    async fn query(
+        &self,
+        filters: &CountryFilters,
+        as_of: Option<DateTime<Utc>>,
+        columns: &[&str],
+    ) -> Result<Vec<Country>, sqlx::Error> {
+        let mut query = QueryBuilder::new("");
+        push_country_source(&mut query, columns, as_of);
 
         if let Some(region) = &filters.region {
             query.push(" AND LOWER(region) = LOWER(");
@@ -84,77 +335,110 @@ impl CountryRepository {
 
         match filters.sort.as_deref() {
             Some("gdp_asc") => query.push(" ORDER BY estimated_gdp ASC"),
-            Some("gdp_desc") | _ => query.push(" ORDER BY estimated_gdp DESC"),
+            _ => query.push(" ORDER BY estimated_gdp DESC"),
         };
 
-        let rows = query
-            .build_query_as::<(
-                i32,
-                String,
-                Option<String>,
-                Option<String>,
-                i64,
-                Option<String>,
-                Option<BigDecimal>,
-                Option<BigDecimal>,
-                Option<String>,
-                DateTime<Utc>,
-            )>()
-            .fetch_all(&self.pool)
-            .await?;
+        fetch_countries(&self.pool, query, columns).await
+    }
 
-        let results = rows
-            .into_iter()
-            .map(|row| Country {
-                id: row.0,
-                name: row.1,
-                capital: row.2,
-                region: row.3,
-                population: row.4,
-                currency_code: row.5,
-                exchange_rate: row.6.and_then(|bd| bd.to_f64()),
-                estimated_gdp: row.7.and_then(|bd| bd.to_f64()),
-                flag_url: row.8,
-                last_refreshed_at: row.9.to_rfc3339_opts(SecondsFormat::Millis, true),
-            })
-            .collect();
+    pub async fn get_by_name(&self, name: &str) -> Result<Option<Country>, sqlx::Error> {
+        if let Some(cache) = &self.cache {
+            let name = name.to_lowercase();
+            return Ok(self
+                .snapshot(cache)
+                .await?
+                .iter()
+                .find(|country| country.name.to_lowercase() == name)
+                .cloned());
+        }
 
-        Ok(results)
+        self.query_by_name(name, None, &COUNTRY_COLUMNS).await
     }
 
-    pub async fn get_by_name(&self, name: &str) -> Result<Option<Country>, sqlx::Error> {
-        let country = query!(
-            r#"
-            SELECT id, name, capital, region, population, currency_code,
-                   exchange_rate, estimated_gdp, flag_url, last_refreshed_at
-            FROM countries
-            WHERE LOWER(name) = LOWER(?)
-            "#,
-            name
-        )
-        .fetch_optional(&self.pool)
-        .await?;
+    /// Countries whose name (case-insensitive) or alpha-2 or alpha-3 code is
+    /// one of `keys`, in one query.
+    pub async fn get_by_names_or_codes(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<Country>, sqlx::Error> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if let Some(cache) = &self.cache {
+            return Ok(self
+                .snapshot(cache)
+                .await?
+                .iter()
+                .filter(|country| keys.iter().any(|key| country.matches(key)))
+                .cloned()
+                .collect());
+        }
+
+        let mut query = QueryBuilder::new("");
+        push_country_source(&mut query, &COUNTRY_COLUMNS, None);
+
+        query.push(" AND (LOWER(name) IN (");
+        let mut names = query.separated(", ");
+        for key in keys {
+            names.push_bind(key.trim().to_lowercase());
+        }
+
+        for column in ["alpha2_code", "alpha3_code"] {
+            query.push(") OR ");
+            query.push(column);
+            query.push(" IN (");
+            let mut codes = query.separated(", ");
+            for key in keys {
+                codes.push_bind(key.trim().to_uppercase());
+            }
+        }
+        query.push(")) ORDER BY name");
+
+        fetch_countries(&self.pool, query, &COUNTRY_COLUMNS).await
+    }
 
-        match country {
-            Some(row) => Ok(Some(Country {
-                id: row.id,
-                name: row.name,
-                capital: row.capital,
-                region: row.region,
-                population: row.population,
-                currency_code: row.currency_code,
-                exchange_rate: row.exchange_rate.and_then(|bd| bd.to_f64()),
-                estimated_gdp: row.estimated_gdp.and_then(|bd| bd.to_f64()),
-                flag_url: row.flag_url,
-                last_refreshed_at: row
-                    .last_refreshed_at
-                    .to_rfc3339_opts(SecondsFormat::Millis, true),
-            })),
-            None => Ok(None),
+    /// The country as it is now, or as stored at `as_of`, reading only the
+    /// columns `fields` needs unless the cache can answer.
+    pub async fn get_by_name_fields(
+        &self,
+        name: &str,
+        as_of: Option<DateTime<Utc>>,
+        fields: &CountryFields,
+    ) -> Result<Option<Country>, sqlx::Error> {
+        match as_of {
+            None if fields.is_all() || self.cache.is_some() => self.get_by_name(name).await,
+            _ => self.query_by_name(name, as_of, &fields.columns()).await,
         }
     }
 
-    pub async fn delete_by_name(&self, name: &str) -> Result<bool, sqlx::Error> {
+    async fn query_by_name(
+        &self,
+        name: &str,
+        as_of: Option<DateTime<Utc>>,
+        columns: &[&str],
+    ) -> Result<Option<Country>, sqlx::Error> {
+        let mut query = QueryBuilder::new("");
+        push_country_source(&mut query, columns, as_of);
+        query.push(" AND LOWER(name) = LOWER(");
+        query.push_bind(name);
+        query.push(")");
+
+        Ok(fetch_countries(&self.pool, query, columns)
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Deletes the country and records `audit_events` in the same
+    /// transaction, so a delete is never left without its audit trail.
+    pub async fn delete_by_name(
+        &self,
+        name: &str,
+        audit_events: &[NewAuditEvent],
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
         let country = query!(
             r#"
             DELETE FROM countries
@@ -162,13 +446,34 @@ impl CountryRepository {
             "#,
             name
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
+        .await?;
+
+        if country.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "UPDATE country_history SET valid_to = ?
+             WHERE valid_to IS NULL AND LOWER(name) = LOWER(?)",
+        )
+        .bind(Utc::now())
+        .bind(name)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(country.rows_affected() > 0)
+        insert_audit_events(&mut tx, audit_events).await?;
+        tx.commit().await?;
+        self.invalidate_cache();
+
+        Ok(true)
     }
 
     pub async fn count(&self) -> Result<i64, sqlx::Error> {
+        if let Some(cache) = &self.cache {
+            return Ok(self.snapshot(cache).await?.len() as i64);
+        }
+
         let result = query!(
             r#"
             SELECT COUNT(*) as count
@@ -182,6 +487,16 @@ impl CountryRepository {
     }
 
     pub async fn get_last_refresh_time(&self) -> Result<Option<String>, sqlx::Error> {
+        if let Some(cache) = &self.cache {
+            // Every timestamp has the same RFC 3339 layout, so they sort as strings
+            return Ok(self
+                .snapshot(cache)
+                .await?
+                .iter()
+                .map(|country| country.last_refreshed_at.clone())
+                .max());
+        }
+
         let result = query!(
             r#"
             SELECT MAX(last_refreshed_at) as last_refresh
@@ -195,4 +510,560 @@ impl CountryRepository {
             .last_refresh
             .map(|ts| ts.to_rfc3339_opts(SecondsFormat::Millis, true)))
     }
+
+    pub async fn count_as_of(&self, as_of: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM (");
+        push_country_source(&mut query, &["id"], Some(as_of));
+        query.push(") AS snapshot");
+
+        let (count,) = query
+            .build_query_as::<(i64,)>()
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    pub async fn get_last_refresh_time_as_of(
+        &self,
+        as_of: DateTime<Utc>,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let mut query = QueryBuilder::new("SELECT MAX(last_refreshed_at) FROM (");
+        push_country_source(&mut query, &["last_refreshed_at"], Some(as_of));
+        query.push(") AS snapshot");
+
+        let (last_refresh,) = query
+            .build_query_as::<(Option<DateTime<Utc>>,)>()
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(last_refresh.map(|ts| ts.to_rfc3339_opts(SecondsFormat::Millis, true)))
+    }
+}
+
+/// Writes audit events on `conn`, so they can share a transaction with the
+/// change they describe.
+async fn insert_audit_events(
+    conn: &mut MySqlConnection,
+    events: &[NewAuditEvent],
+) -> Result<usize, sqlx::Error> {
+    const BATCH_SIZE: usize = 100;
+    let mut total_saved = 0;
+
+    for chunk in events.chunks(BATCH_SIZE) {
+        let mut query_builder = QueryBuilder::new(
+            "INSERT INTO audit_events (country_name, action, source, actor, changes)",
+        );
+
+        query_builder.push_values(chunk, |mut b, event| {
+            b.push_bind(&event.country_name)
+                .push_bind(event.action.as_str())
+                .push_bind(&event.source)
+                .push_bind(&event.actor)
+                .push_bind(Json(&event.changes));
+        });
+
+        let result = query_builder.build().execute(&mut *conn).await?;
+        total_saved += result.rows_affected() as usize;
+    }
+
+    Ok(total_saved)
+}
+
+#[derive(Clone)]
+pub struct AuditRepository {
+    pool: DbPool,
+}
+
+impl AuditRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn record(&self, events: &[NewAuditEvent]) -> Result<usize, sqlx::Error> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        insert_audit_events(&mut conn, events).await
+    }
+
+    pub async fn list(
+        &self,
+        country: Option<&str>,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        let mut query = QueryBuilder::new(
+            "SELECT id, country_name, action, source, actor, changes, created_at
+         FROM audit_events WHERE 1=1",
+        );
+
+        if let Some(country) = country {
+            query.push(" AND LOWER(country_name) = LOWER(");
+            query.push_bind(country);
+            query.push(")");
+        }
+
+        if let Some(since) = since {
+            query.push(" AND created_at >= ");
+            query.push_bind(since);
+        }
+
+        query.push(" ORDER BY created_at ASC, id ASC");
+
+        let rows = query
+            .build_query_as::<(
+                i64,
+                String,
+                String,
+                String,
+                Option<String>,
+                Json<Value>,
+                DateTime<Utc>,
+            )>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        let results = rows
+            .into_iter()
+            .map(|row| AuditEvent {
+                id: row.0,
+                country_name: row.1,
+                action: row.2,
+                source: row.3,
+                actor: row.4,
+                changes: row.5.0,
+                created_at: row.6.to_rfc3339_opts(SecondsFormat::Millis, true),
+            })
+            .collect();
+
+        Ok(results)
+    }
+}
+
+#[derive(Clone)]
+pub struct RefreshRepository {
+    pool: DbPool,
+}
+
+impl RefreshRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn start(&self, started_at: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query("INSERT INTO refreshes (status, started_at) VALUES (?, ?)")
+            .bind(RefreshStatus::Running.as_str())
+            .bind(started_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.last_insert_id() as i64)
+    }
+
+    pub async fn complete(&self, id: i64, diff: &RefreshDiff) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE refreshes SET status = ?, completed_at = CURRENT_TIMESTAMP(3), diff = ?
+             WHERE id = ?",
+        )
+        .bind(RefreshStatus::Completed.as_str())
+        .bind(Json(diff))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn fail(&self, id: i64, error: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE refreshes SET status = ?, completed_at = CURRENT_TIMESTAMP(3), error = ?
+             WHERE id = ?",
+        )
+        .bind(RefreshStatus::Failed.as_str())
+        .bind(error)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<Refresh>, sqlx::Error> {
+        let row = sqlx::query_as::<_, (i64, String, Option<Json<RefreshDiff>>)>(
+            "SELECT id, status, diff FROM refreshes WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| Refresh {
+            id: row.0,
+            status: row.1,
+            diff: row.2.map(|diff| diff.0),
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub struct QuarantineRepository {
+    pool: DbPool,
+}
+
+impl QuarantineRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn record(
+        &self,
+        refresh_id: i64,
+        records: &[NewQuarantinedRecord],
+    ) -> Result<usize, sqlx::Error> {
+        if records.is_empty() {
+            return Ok(0);
+        }
+
+        const BATCH_SIZE: usize = 100;
+        let mut total_saved = 0;
+
+        for chunk in records.chunks(BATCH_SIZE) {
+            let mut query_builder = QueryBuilder::new(
+                "INSERT INTO quarantined_records (refresh_id, source, country_name, reason, payload)",
+            );
+
+            query_builder.push_values(chunk, |mut b, record| {
+                b.push_bind(refresh_id)
+                    .push_bind(&record.source)
+                    .push_bind(&record.country_name)
+                    .push_bind(&record.reason)
+                    .push_bind(Json(&record.payload));
+            });
+
+            let result = query_builder.build().execute(&self.pool).await?;
+            total_saved += result.rows_affected() as usize;
+        }
+
+        Ok(total_saved)
+    }
+
+    pub async fn list_for_refresh(
+        &self,
+        refresh_id: i64,
+    ) -> Result<Vec<QuarantinedRecord>, sqlx::Error> {
+        let rows = sqlx::query_as::<
+            _,
+            (
+                i64,
+                i64,
+                String,
+                Option<String>,
+                String,
+                Json<Value>,
+                DateTime<Utc>,
+            ),
+        >(
+            "SELECT id, refresh_id, source, country_name, reason, payload, created_at
+             FROM quarantined_records WHERE refresh_id = ? ORDER BY id ASC",
+        )
+        .bind(refresh_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let results = rows
+            .into_iter()
+            .map(|row| QuarantinedRecord {
+                id: row.0,
+                refresh_id: row.1,
+                source: row.2,
+                country_name: row.3,
+                reason: row.4,
+                payload: row.5.0,
+                created_at: row.6.to_rfc3339_opts(SecondsFormat::Millis, true),
+            })
+            .collect();
+
+        Ok(results)
+    }
+}
+
+#[derive(Clone)]
+pub struct RateOverrideRepository {
+    pool: DbPool,
+    cache: Option<CountryCache>,
+}
+
+type RateOverrideRow = (String, BigDecimal, String, DateTime<Utc>, DateTime<Utc>);
+
+fn rate_override_from_row(row: RateOverrideRow) -> RateOverride {
+    RateOverride {
+        currency_code: row.0,
+        rate: row.1,
+        reason: row.2,
+        expires_at: row.3.to_rfc3339_opts(SecondsFormat::Millis, true),
+        updated_at: row.4.to_rfc3339_opts(SecondsFormat::Millis, true),
+    }
+}
+
+impl RateOverrideRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool, cache: None }
+    }
+
+    /// Serves active overrides from `cache`, the country repository's, and
+    /// clears it whenever an override is written.
+    pub fn with_cache(pool: DbPool, cache: CountryCache) -> Self {
+        Self {
+            pool,
+            cache: Some(cache),
+        }
+    }
+
+    fn invalidate_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate();
+        }
+    }
+
+    /// Stores the override, recording `audit_events` for the countries whose
+    /// rate it changes in the same transaction.
+    pub async fn upsert(
+        &self,
+        currency_code: &str,
+        rate: &BigDecimal,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+        audit_events: &[NewAuditEvent],
+    ) -> Result<RateOverride, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO rate_overrides (currency_code, rate, reason, expires_at)
+             VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE
+                rate = VALUES(rate),
+                reason = VALUES(reason),
+                expires_at = VALUES(expires_at)",
+        )
+        .bind(currency_code)
+        .bind(rate)
+        .bind(reason)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        let row = sqlx::query_as::<_, RateOverrideRow>(
+            "SELECT currency_code, rate, reason, expires_at, updated_at
+             FROM rate_overrides WHERE currency_code = ?",
+        )
+        .bind(currency_code)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        insert_audit_events(&mut tx, audit_events).await?;
+        tx.commit().await?;
+        self.invalidate_cache();
+
+        Ok(rate_override_from_row(row))
+    }
+
+    /// Removes the override, recording `audit_events` in the same transaction.
+    /// Returns false, recording nothing, when there was no override.
+    pub async fn delete(
+        &self,
+        currency_code: &str,
+        audit_events: &[NewAuditEvent],
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query("DELETE FROM rate_overrides WHERE currency_code = ?")
+            .bind(currency_code)
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        insert_audit_events(&mut tx, audit_events).await?;
+        tx.commit().await?;
+        self.invalidate_cache();
+
+        Ok(true)
+    }
+
+    /// Override rates that have not expired at `now`, keyed by currency code.
+    pub async fn active_rates(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<HashMap<String, BigDecimal>, sqlx::Error> {
+        Ok(self.active_overrides(now).await?.rates)
+    }
+
+    /// The overrides that have not expired at `now`, with their expiries.
+    /// Cached entries are dropped once the first of them expires.
+    pub async fn active_overrides(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<ActiveOverrides, sqlx::Error> {
+        let Some(cache) = &self.cache else {
+            return self.query_active_overrides(now).await;
+        };
+
+        if let Some(overrides) = cache.overrides(now) {
+            return Ok(overrides.as_ref().clone());
+        }
+
+        let generation = cache.generation();
+        let overrides = self.query_active_overrides(now).await?;
+
+        Ok(cache
+            .store_overrides(generation, overrides)
+            .as_ref()
+            .clone())
+    }
+
+    async fn query_active_overrides(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<ActiveOverrides, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String, BigDecimal, DateTime<Utc>)>(
+            "SELECT currency_code, rate, expires_at FROM rate_overrides WHERE expires_at > ?",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut overrides = ActiveOverrides::default();
+        for (code, rate, expires_at) in rows {
+            overrides.expires_at.insert(code.clone(), expires_at);
+            overrides.rates.insert(code, rate);
+        }
+
+        Ok(overrides)
+    }
+}
+
+/// How stale `api_keys.last_used_at` may get before a request rewrites it.
+const LAST_USED_AT_PRECISION: chrono::TimeDelta = chrono::TimeDelta::minutes(1);
+
+#[derive(Clone)]
+pub struct ApiKeyRepository {
+    pool: DbPool,
+}
+
+type ApiKeyRow = (
+    i64,
+    String,
+    String,
+    String,
+    DateTime<Utc>,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+);
+
+fn api_key_from_row(row: ApiKeyRow) -> Result<ApiKey, sqlx::Error> {
+    Ok(ApiKey {
+        id: row.0,
+        name: row.1,
+        role: row
+            .2
+            .parse::<Role>()
+            .map_err(|e| sqlx::Error::Decode(e.into()))?,
+        key_prefix: row.3,
+        created_at: row.4.to_rfc3339_opts(SecondsFormat::Millis, true),
+        last_used_at: row
+            .5
+            .map(|at| at.to_rfc3339_opts(SecondsFormat::Millis, true)),
+        revoked_at: row
+            .6
+            .map(|at| at.to_rfc3339_opts(SecondsFormat::Millis, true)),
+    })
+}
+
+impl ApiKeyRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores a key by its hash; the plaintext key is never written.
+    pub async fn create(
+        &self,
+        name: &str,
+        role: Role,
+        key_prefix: &str,
+        key_hash: &str,
+    ) -> Result<ApiKey, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO api_keys (name, role, key_prefix, key_hash) VALUES (?, ?, ?, ?)",
+        )
+        .bind(name)
+        .bind(role.as_str())
+        .bind(key_prefix)
+        .bind(key_hash)
+        .execute(&self.pool)
+        .await?;
+
+        let row = sqlx::query_as::<_, ApiKeyRow>(
+            "SELECT id, name, role, key_prefix, created_at, last_used_at, revoked_at
+             FROM api_keys WHERE id = ?",
+        )
+        .bind(result.last_insert_id() as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        api_key_from_row(row)
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, ApiKeyRow>(
+            "SELECT id, name, role, key_prefix, created_at, last_used_at, revoked_at
+             FROM api_keys ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(api_key_from_row).collect()
+    }
+
+    /// Finds an unrevoked key by hash and records that it was used.
+    pub async fn authenticate(&self, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        let row = sqlx::query_as::<_, ApiKeyRow>(
+            "SELECT id, name, role, key_prefix, created_at, last_used_at, revoked_at
+             FROM api_keys WHERE key_hash = ? AND revoked_at IS NULL",
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        // Only kept to the minute, so most requests stay read-only
+        if row
+            .5
+            .is_none_or(|last_used_at| Utc::now() - last_used_at >= LAST_USED_AT_PRECISION)
+        {
+            sqlx::query("UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP(3) WHERE id = ?")
+                .bind(row.0)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        api_key_from_row(row).map(Some)
+    }
+
+    pub async fn revoke(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP(3)
+             WHERE id = ? AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
 }
@@ -0,0 +1,212 @@
+use axum::{
+    Json,
+    http::{
+        HeaderValue,
+        header::{CONTENT_TYPE, RETRY_AFTER},
+    },
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    middleware::current_request_context,
+    models::responses::{ApiError, ProblemDetails},
+};
+
+/// Error body layout, set with `ERROR_FORMAT`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    /// `ApiError` JSON, unless the client asks for `application/problem+json`
+    #[default]
+    Json,
+    /// RFC 7807 `application/problem+json` for every error
+    Problem,
+}
+
+/// Errors returned by handlers. Each maps to a status and a stable `code`;
+/// server-side failures are logged and answered with a generic message.
+#[derive(Debug)]
+pub enum AppError {
+    /// Per-field messages, keyed by field name
+    Validation(Value),
+    NotFound(String),
+    /// No credentials, or credentials that don't match an active key
+    Unauthorized(String),
+    /// Valid credentials without the required scope
+    Forbidden(String),
+    /// The resource exists but is not in a state that allows the request
+    Conflict {
+        code: &'static str,
+        message: String,
+        details: Option<Value>,
+    },
+    /// The client's rate limit budget is spent
+    RateLimited {
+        retry_after_secs: u64,
+    },
+    /// The request body is larger than `MAX_BODY_BYTES`
+    PayloadTooLarge {
+        limit: usize,
+    },
+    /// The request took longer than `REQUEST_TIMEOUT_SECS`
+    Timeout,
+    /// Every upstream that could have served the request failed
+    UpstreamUnavailable(String),
+    Database(sqlx::Error),
+    Http(reqwest::Error),
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    pub fn validation(field: &str, message: &str) -> Self {
+        Self::Validation(serde_json::json!({ field: message }))
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) | AppError::Database(sqlx::Error::RowNotFound) => {
+                StatusCode::NOT_FOUND
+            }
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UpstreamUnavailable(_)
+            | AppError::Timeout
+            | AppError::Database(sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            AppError::Http(e) if e.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Http(_) => StatusCode::BAD_GATEWAY,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation(_) => "validation_failed",
+            AppError::NotFound(_) | AppError::Database(sqlx::Error::RowNotFound) => "not_found",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::Conflict { code, .. } => code,
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::PayloadTooLarge { .. } => "payload_too_large",
+            AppError::Timeout => "request_timeout",
+            AppError::UpstreamUnavailable(_) => "upstream_unavailable",
+            AppError::Database(sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed) => {
+                "database_unavailable"
+            }
+            AppError::Database(_) => "database_error",
+            AppError::Http(e) if e.is_timeout() => "upstream_timeout",
+            AppError::Http(_) => "upstream_error",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            AppError::Validation(_) => "Validation failed".to_string(),
+            AppError::NotFound(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::Conflict { message, .. } => message.clone(),
+            AppError::RateLimited { retry_after_secs } => {
+                format!("Too many requests; retry in {} seconds", retry_after_secs)
+            }
+            AppError::PayloadTooLarge { limit } => {
+                format!("Request body is larger than {} bytes", limit)
+            }
+            AppError::Timeout => "Request timed out".to_string(),
+            AppError::UpstreamUnavailable(_) => "External data source unavailable".to_string(),
+            AppError::Database(sqlx::Error::RowNotFound) => "Not found".to_string(),
+            AppError::Database(sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed) => {
+                "Database unavailable".to_string()
+            }
+            AppError::Http(_) => "Upstream request failed".to_string(),
+            AppError::Database(_) | AppError::Internal(_) => "Internal server error".to_string(),
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match self {
+            AppError::Validation(details) => Some(details.clone()),
+            AppError::Conflict { details, .. } => details.clone(),
+            AppError::UpstreamUnavailable(detail) => Some(Value::String(detail.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let context = current_request_context();
+        let request_id = context.as_ref().map(|context| context.id.clone());
+
+        match &self {
+            AppError::Database(_) | AppError::Http(_) | AppError::Internal(_) => {
+                tracing::error!(request_id = request_id.as_deref(), "{:?}", self);
+            }
+            _ => {}
+        }
+
+        let mut response = if context.is_some_and(|context| context.problem_json) {
+            let body = ProblemDetails {
+                problem_type: "about:blank".to_string(),
+                title: status.canonical_reason().unwrap_or_default().to_string(),
+                status: status.as_u16(),
+                detail: self.message(),
+                code: self.code().to_string(),
+                request_id,
+                details: self.details(),
+            };
+
+            let mut response = (status, Json(body)).into_response();
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            );
+            response
+        } else {
+            let body = ApiError {
+                error: self.message(),
+                code: self.code().to_string(),
+                request_id,
+                details: self.details(),
+            };
+
+            (status, Json(body)).into_response()
+        };
+
+        if let AppError::RateLimited { retry_after_secs } = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        }
+
+        response
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        AppError::Database(error)
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(error: reqwest::Error) -> Self {
+        AppError::Http(error)
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        AppError::Internal(error)
+    }
+}
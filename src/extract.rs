@@ -0,0 +1,158 @@
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, OptionalFromRequest, Request},
+    http::{StatusCode, header::CONTENT_TYPE, request::Parts},
+};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value, json};
+
+use crate::{error::AppError, models::state::AppState};
+
+/// Query parameters accepted by an endpoint, with checks beyond their types.
+pub trait QueryParams: DeserializeOwned {
+    /// Every accepted parameter name. Anything else is rejected.
+    const FIELDS: &'static [&'static str];
+
+    /// Messages for invalid values, keyed by parameter name.
+    fn validate(&self) -> Map<String, Value> {
+        Map::new()
+    }
+}
+
+/// Like `Query`, but rejects unknown parameters and invalid values with a
+/// `validation_failed` error listing every offending parameter.
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: QueryParams,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+
+        let pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+            .map_err(|_| AppError::validation("query", "is not a valid query string"))?;
+
+        let mut errors = Map::new();
+        let mut known = Vec::new();
+
+        for (name, value) in pairs {
+            if !T::FIELDS.contains(&name.as_str()) {
+                errors.insert(
+                    name,
+                    json!(format!(
+                        "unknown parameter; expected one of: {}",
+                        T::FIELDS.join(", ")
+                    )),
+                );
+                continue;
+            }
+
+            // Parsed one at a time so a type error can be pinned to its parameter
+            let single = serde_urlencoded::to_string([(&name, &value)]).unwrap_or_default();
+            match serde_urlencoded::from_str::<T>(&single) {
+                Ok(_) => known.push((name, value)),
+                Err(e) => {
+                    errors.insert(name, json!(e.to_string()));
+                }
+            }
+        }
+
+        let known = serde_urlencoded::to_string(&known).unwrap_or_default();
+        let params = match serde_urlencoded::from_str::<T>(&known) {
+            Ok(params) => params,
+            Err(e) => {
+                errors.insert("query".to_string(), json!(e.to_string()));
+                return Err(AppError::Validation(Value::Object(errors)));
+            }
+        };
+
+        errors.extend(params.validate());
+        if !errors.is_empty() {
+            return Err(AppError::Validation(Value::Object(errors)));
+        }
+
+        Ok(ValidatedQuery(params))
+    }
+}
+
+/// Like `Json`, but answers a missing content type, malformed JSON or a
+/// mistyped field with a `validation_failed` error naming the offending field.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> FromRequest<AppState> for ValidatedJson<T>
+where
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        if !has_json_content_type(&req) {
+            return Err(AppError::validation(
+                "body",
+                "must be sent with Content-Type: application/json",
+            ));
+        }
+
+        let bytes = Bytes::from_request(req, state).await.map_err(|rejection| {
+            match rejection.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge {
+                    limit: state.config.max_body_bytes,
+                },
+                _ => AppError::validation("body", "could not be read"),
+            }
+        })?;
+
+        parse_json(&bytes).map(ValidatedJson)
+    }
+}
+
+/// An optional body: `None` when the request has no `Content-Type`, as for
+/// `Option<Json<T>>`, and rejected like `ValidatedJson` otherwise.
+impl<T> OptionalFromRequest<AppState> for ValidatedJson<T>
+where
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Option<Self>, Self::Rejection> {
+        if !req.headers().contains_key(CONTENT_TYPE) {
+            return Ok(None);
+        }
+
+        <Self as FromRequest<AppState>>::from_request(req, state)
+            .await
+            .map(Some)
+    }
+}
+
+fn has_json_content_type(req: &Request) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(str::trim)
+        .is_some_and(|mime| {
+            mime.eq_ignore_ascii_case("application/json")
+                || mime.to_ascii_lowercase().ends_with("+json")
+        })
+}
+
+fn parse_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, AppError> {
+    // Parsed in two steps so syntax errors and type errors are told apart, and
+    // a type error can be pinned to its field
+    let value = serde_json::from_slice::<Value>(bytes)
+        .map_err(|_| AppError::validation("body", "is not valid JSON"))?;
+
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let field = match e.path().to_string() {
+            path if path == "." => "body".to_string(),
+            path => path,
+        };
+
+        AppError::validation(&field, &e.inner().to_string())
+    })
+}
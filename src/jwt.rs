@@ -0,0 +1,353 @@
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use reqwest::Client;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{models::scope::Scope, utils::config::Config};
+
+/// Tokens signed with an unknown `kid` refetch a `JWKS_URL` at most this
+/// often, so key rotation is picked up without letting forged tokens hammer it.
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+enum JwksSource {
+    File(String),
+    Url(String),
+}
+
+impl JwksSource {
+    async fn load(&self, client: &Client) -> Result<Vec<Jwk>> {
+        let body = match self {
+            JwksSource::File(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read JWKS file {}", path))?,
+            JwksSource::Url(url) => {
+                client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?
+            }
+        };
+
+        parse_jwks(&body)
+    }
+}
+
+/// Public key material for the algorithms we accept. Anything else in the
+/// JWKS (encryption keys, RSA-PSS, other curves) is skipped.
+#[derive(Debug, Clone)]
+enum KeyMaterial {
+    /// RS256
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    /// ES256, as an uncompressed `0x04 || x || y` point
+    EcdsaP256(Vec<u8>),
+    /// EdDSA
+    Ed25519(Vec<u8>),
+}
+
+impl KeyMaterial {
+    fn algorithm(&self) -> &'static str {
+        match self {
+            KeyMaterial::Rsa { .. } => "RS256",
+            KeyMaterial::EcdsaP256(_) => "ES256",
+            KeyMaterial::Ed25519(_) => "EdDSA",
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            KeyMaterial::Rsa { n, e } => RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, signature)
+                .is_ok(),
+            KeyMaterial::EcdsaP256(point) => {
+                UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                    .verify(message, signature)
+                    .is_ok()
+            }
+            KeyMaterial::Ed25519(key) => UnparsedPublicKey::new(&signature::ED25519, key)
+                .verify(message, signature)
+                .is_ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Jwk {
+    kid: Option<String>,
+    material: KeyMaterial,
+}
+
+#[derive(Deserialize)]
+struct RawJwks {
+    keys: Vec<RawJwk>,
+}
+
+#[derive(Deserialize)]
+struct RawJwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    crv: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+impl RawJwk {
+    fn into_jwk(self) -> Result<Jwk> {
+        if self
+            .key_use
+            .as_deref()
+            .is_some_and(|key_use| key_use != "sig")
+        {
+            bail!("not a signing key");
+        }
+
+        let field = |value: &Option<String>, name: &str| {
+            value
+                .as_deref()
+                .ok_or_else(|| anyhow!("missing {:?}", name))
+                .and_then(decode)
+        };
+
+        let material = match (self.kty.as_str(), self.crv.as_deref()) {
+            ("RSA", _) => KeyMaterial::Rsa {
+                n: field(&self.n, "n")?,
+                e: field(&self.e, "e")?,
+            },
+            ("EC", Some("P-256")) => {
+                let mut point = vec![0x04];
+                point.extend(field(&self.x, "x")?);
+                point.extend(field(&self.y, "y")?);
+                KeyMaterial::EcdsaP256(point)
+            }
+            ("OKP", Some("Ed25519")) => KeyMaterial::Ed25519(field(&self.x, "x")?),
+            (kty, crv) => bail!("unsupported key type {} {}", kty, crv.unwrap_or_default()),
+        };
+
+        if let Some(alg) = &self.alg
+            && alg != material.algorithm()
+        {
+            bail!("unsupported algorithm {}", alg);
+        }
+
+        Ok(Jwk {
+            kid: self.kid,
+            material,
+        })
+    }
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| anyhow!("invalid base64url"))
+}
+
+fn parse_jwks(body: &str) -> Result<Vec<Jwk>> {
+    let jwks = serde_json::from_str::<RawJwks>(body).context("Invalid JWKS")?;
+
+    let keys = jwks
+        .keys
+        .into_iter()
+        .filter_map(|raw| {
+            let kid = raw.kid.clone();
+            raw.into_jwk()
+                .inspect_err(|e| tracing::warn!("Skipping JWKS key {:?}: {}", kid, e))
+                .ok()
+        })
+        .collect::<Vec<_>>();
+
+    if keys.is_empty() {
+        bail!("JWKS has no usable RS256, ES256 or EdDSA signing keys");
+    }
+
+    Ok(keys)
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// Checks bearer tokens against a JWKS loaded from `JWKS_FILE` or
+/// `JWKS_URL`, and against the configured issuer and audience.
+#[derive(Clone)]
+pub struct JwtVerifier {
+    source: JwksSource,
+    client: Client,
+    issuer: String,
+    audience: String,
+    leeway_secs: u64,
+    keys: Arc<RwLock<Vec<Jwk>>>,
+    last_fetched: Arc<Mutex<Instant>>,
+}
+
+impl JwtVerifier {
+    /// `None` unless a JWKS is configured. Fails if it can't be loaded.
+    pub async fn from_config(config: &Config, client: &Client) -> Result<Option<Self>> {
+        let source = match (&config.jwks_file, &config.jwks_url) {
+            (Some(path), _) => JwksSource::File(path.clone()),
+            (None, Some(url)) => JwksSource::Url(url.clone()),
+            (None, None) => return Ok(None),
+        };
+
+        let issuer = config
+            .jwt_issuer
+            .clone()
+            .ok_or_else(|| anyhow!("JWT_ISSUER is required with a JWKS"))?;
+        let audience = config
+            .jwt_audience
+            .clone()
+            .ok_or_else(|| anyhow!("JWT_AUDIENCE is required with a JWKS"))?;
+
+        let keys = source.load(client).await?;
+
+        Ok(Some(Self {
+            source,
+            client: client.clone(),
+            issuer,
+            audience,
+            leeway_secs: config.jwt_leeway_secs,
+            keys: Arc::new(RwLock::new(keys)),
+            last_fetched: Arc::new(Mutex::new(Instant::now())),
+        }))
+    }
+
+    /// Verifies the signature and registered claims, returning every claim.
+    /// Errors say why the token was rejected.
+    pub async fn verify(&self, token: &str) -> Result<Map<String, Value>> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("malformed token");
+        };
+
+        let parsed_header = serde_json::from_slice::<Header>(&decode(header)?)
+            .map_err(|_| anyhow!("malformed token header"))?;
+        let claims = serde_json::from_slice::<Map<String, Value>>(&decode(payload)?)
+            .map_err(|_| anyhow!("malformed token claims"))?;
+        let signature = decode(signature)?;
+
+        if !["RS256", "ES256", "EdDSA"].contains(&parsed_header.alg.as_str()) {
+            bail!("unsupported algorithm {}", parsed_header.alg);
+        }
+
+        if let Some(kid) = &parsed_header.kid {
+            self.refetch_if_unknown(kid).await;
+        }
+
+        let message = format!("{}.{}", header, payload);
+        let verified = self
+            .keys
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|key| key.material.algorithm() == parsed_header.alg)
+            .filter(|key| parsed_header.kid.is_none() || key.kid == parsed_header.kid)
+            .any(|key| key.material.verify(message.as_bytes(), &signature));
+
+        if !verified {
+            bail!("invalid signature");
+        }
+
+        self.check_claims(&claims)?;
+
+        Ok(claims)
+    }
+
+    fn check_claims(&self, claims: &Map<String, Value>) -> Result<()> {
+        let now = Utc::now().timestamp() as f64;
+        let leeway = self.leeway_secs as f64;
+
+        let exp = claims
+            .get("exp")
+            .and_then(Value::as_f64)
+            .ok_or_else(|| anyhow!("token has no expiry"))?;
+        if now > exp + leeway {
+            bail!("token has expired");
+        }
+
+        if let Some(nbf) = claims.get("nbf").and_then(Value::as_f64)
+            && now + leeway < nbf
+        {
+            bail!("token is not valid yet");
+        }
+
+        if claims.get("iss").and_then(Value::as_str) != Some(self.issuer.as_str()) {
+            bail!("unexpected issuer");
+        }
+
+        let audience_matches = match claims.get("aud") {
+            Some(Value::String(aud)) => *aud == self.audience,
+            Some(Value::Array(auds)) => auds
+                .iter()
+                .any(|aud| aud.as_str() == Some(self.audience.as_str())),
+            _ => false,
+        };
+        if !audience_matches {
+            bail!("unexpected audience");
+        }
+
+        Ok(())
+    }
+
+    async fn refetch_if_unknown(&self, kid: &str) {
+        let JwksSource::Url(url) = &self.source else {
+            return;
+        };
+
+        let known = self
+            .keys
+            .read()
+            .unwrap()
+            .iter()
+            .any(|key| key.kid.as_deref() == Some(kid));
+        if known {
+            return;
+        }
+
+        {
+            let mut last_fetched = self.last_fetched.lock().unwrap();
+            if last_fetched.elapsed() < JWKS_REFETCH_INTERVAL {
+                return;
+            }
+            *last_fetched = Instant::now();
+        }
+
+        match self.source.load(&self.client).await {
+            Ok(keys) => *self.keys.write().unwrap() = keys,
+            Err(e) => tracing::warn!("Failed to refetch JWKS from {}: {:?}", url, e),
+        }
+    }
+}
+
+/// Scopes granted by the `scope` claim (space-separated) or `scp` (a list or
+/// a space-separated string). Scopes this service doesn't know are ignored.
+pub fn claim_scopes(claims: &Map<String, Value>) -> Vec<Scope> {
+    let names = match claims.get("scope").or_else(|| claims.get("scp")) {
+        Some(Value::String(scopes)) => scopes.split_whitespace().collect::<Vec<_>>(),
+        Some(Value::Array(scopes)) => scopes.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+
+    names
+        .into_iter()
+        .filter_map(|name| name.parse().ok())
+        .collect()
+}
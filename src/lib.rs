@@ -1,5 +1,11 @@
 pub mod api;
+pub mod auth;
+pub mod cli;
 pub mod db;
+pub mod error;
+pub mod extract;
+pub mod jwt;
+pub mod middleware;
 pub mod models;
 pub mod routes;
 pub mod utils;
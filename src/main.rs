@@ -1,10 +1,24 @@
 use anyhow::{Ok, Result};
 use currency_exchange_api::{
     api::build_router,
-    db::{pool::create_pool, repositories::CountryRepository},
+    cli::api_keys_command,
+    db::{
+        migrations::{run_migrations, verify_schema},
+        pool::create_pool,
+        repositories::{
+            ApiKeyRepository, AuditRepository, CountryRepository, QuarantineRepository,
+            RateOverrideRepository, RefreshRepository,
+        },
+    },
+    jwt::JwtVerifier,
     models::state::AppState,
-    utils::config::load_config,
+    utils::{
+        config::load_config, decimal::set_decimal_format, http::HttpClients,
+        rate_limit::RateLimits, resilience::CircuitBreakers, upstream_cache::UpstreamCache,
+    },
 };
+use std::{net::SocketAddr, time::Duration};
+
 use tokio::net::TcpListener;
 
 #[tokio::main]
@@ -14,6 +28,8 @@ async fn main() -> Result<()> {
     let config = load_config()?;
     tracing::info!("Configuration loaded successfully");
 
+    set_decimal_format(config.decimal_format);
+
     let pool = create_pool(
         &config.database_url,
         config.database_max_connections,
@@ -22,17 +38,66 @@ async fn main() -> Result<()> {
     .await?;
     tracing::info!("Database connection pool created");
 
+    if config.run_migrations {
+        run_migrations(&pool).await?;
+        tracing::info!("Database migrations applied");
+    }
+
+    let schema_version = verify_schema(&pool).await?;
+    tracing::info!("Database schema version: {:?}", schema_version);
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some((command, rest)) = args.split_first()
+        && command == "api-keys"
+    {
+        return api_keys_command(rest, &ApiKeyRepository::new(pool)).await;
+    }
+
     let address = format!("{}:{}", config.server_host, &config.server_port);
 
-    let repository = CountryRepository::new(pool);
-    let state = AppState { repository, config };
+    let http = HttpClients::from_config(&config)?;
+    let jwt = JwtVerifier::from_config(&config, &http.jwks).await?;
+    let breakers = CircuitBreakers::from_config(&config);
+    let rate_limits = RateLimits::from_config(&config);
+    let repository = match config.country_cache_ttl_secs {
+        0 => CountryRepository::new(pool.clone()),
+        ttl => CountryRepository::with_cache(pool.clone(), Duration::from_secs(ttl)),
+    };
+    let audit = AuditRepository::new(pool.clone());
+    let refreshes = RefreshRepository::new(pool.clone());
+    let quarantine = QuarantineRepository::new(pool.clone());
+    let overrides = match repository.cache() {
+        Some(cache) => RateOverrideRepository::with_cache(pool.clone(), cache),
+        None => RateOverrideRepository::new(pool.clone()),
+    };
+    let api_keys = ApiKeyRepository::new(pool);
+    let state = AppState {
+        repository,
+        audit,
+        refreshes,
+        quarantine,
+        overrides,
+        api_keys,
+        jwt,
+        config,
+        schema_version,
+        http,
+        breakers,
+        upstream_cache: UpstreamCache::new(),
+        rate_limits,
+    };
 
     let app = build_router(state);
 
     let listener = TcpListener::bind(&address).await?;
     tracing::info!("Server running on {}", address);
 
-    axum::serve(listener, app).await?;
+    // Peer addresses key the rate limits of clients without credentials
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,335 @@
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+        header::{
+            ACCEPT, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
+            IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER, WARNING,
+        },
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tower_http::{
+    compression::{
+        CompressionLayer,
+        predicate::{NotForContentType, Predicate, SizeAbove},
+    },
+    cors::{AllowOrigin, CorsLayer},
+    request_id::{MakeRequestId, RequestId},
+};
+use tracing::Instrument;
+
+use crate::{
+    auth::{API_KEY_HEADER, Principal},
+    error::{AppError, ErrorFormat},
+    models::state::AppState,
+    utils::{
+        config::Config,
+        rate_limit::{RateBudget, RateLimitStatus},
+    },
+};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Per-request values error responses need but handlers don't pass around.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub id: String,
+    /// Whether errors should be written as `application/problem+json`
+    pub problem_json: bool,
+}
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// The context of the request being handled, if called inside `request_context`.
+pub fn current_request_context() -> Option<RequestContext> {
+    REQUEST_CONTEXT.try_with(RequestContext::clone).ok()
+}
+
+/// Generates request IDs for tower-http's `SetRequestIdLayer`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MakeHexRequestId;
+
+impl MakeRequestId for MakeHexRequestId {
+    fn make_request_id<B>(&mut self, _request: &axum::http::Request<B>) -> Option<RequestId> {
+        HeaderValue::from_str(&new_request_id())
+            .ok()
+            .map(RequestId::new)
+    }
+}
+
+fn new_request_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// Makes the request's `X-Request-Id` available to error responses, log
+/// lines and upstream requests. Set and echoed by tower-http's request ID
+/// layers; a malformed or missing one is replaced here.
+pub async fn request_context(
+    State(format): State<ErrorFormat>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let incoming = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string);
+    let replaced = incoming.is_none();
+    let id = incoming.unwrap_or_else(new_request_id);
+
+    let problem_json = format == ErrorFormat::Problem
+        || request
+            .headers()
+            .get(ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/problem+json"));
+
+    let context = RequestContext {
+        id: id.clone(),
+        problem_json,
+    };
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = %request.uri().path()
+    );
+    let mut response = REQUEST_CONTEXT
+        .scope(context, next.run(request))
+        .instrument(span)
+        .await;
+
+    // Takes precedence over the incoming ID `PropagateRequestIdLayer` echoes
+    if replaced && let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    response
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Takes a token from the caller's `budget` when `RATE_LIMIT_ENABLED` is set,
+/// answering `429` once it is spent. Runs after authentication, so callers
+/// with credentials are keyed by who they are rather than where they are.
+pub async fn rate_limit(
+    State((state, budget)): State<(AppState, RateBudget)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.config.rate_limit_enabled {
+        return next.run(request).await;
+    }
+
+    let client = client_key(&request, state.config.rate_limit_trust_forwarded_for);
+    let status = state.rate_limits.get(budget).check(&client);
+
+    let mut response = if status.allowed {
+        next.run(request).await
+    } else {
+        AppError::RateLimited {
+            retry_after_secs: status.retry_after_secs,
+        }
+        .into_response()
+    };
+
+    insert_rate_limit_headers(response.headers_mut(), &status);
+    response
+}
+
+/// Counts requests answered `401` against the caller's address when both
+/// `AUTH_ENABLED` and `RATE_LIMIT_ENABLED` are set, and answers `429` once the
+/// budget is spent without looking the credentials up. Runs before
+/// authentication, so guessed keys and tokens are limited too.
+pub async fn limit_auth_failures(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !(state.config.auth_enabled && state.config.rate_limit_enabled) {
+        return next.run(request).await;
+    }
+
+    let client = address_key(&request, state.config.rate_limit_trust_forwarded_for);
+    let failures = state.rate_limits.get(RateBudget::AuthFailure);
+
+    let status = failures.peek(&client);
+    if !status.allowed {
+        let mut response = AppError::RateLimited {
+            retry_after_secs: status.retry_after_secs,
+        }
+        .into_response();
+        insert_rate_limit_headers(response.headers_mut(), &status);
+        return response;
+    }
+
+    let response = next.run(request).await;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        failures.check(&client);
+    }
+
+    response
+}
+
+fn client_key(request: &Request, trust_forwarded_for: bool) -> String {
+    if let Some(principal) = request.extensions().get::<Principal>() {
+        return principal.id.clone();
+    }
+
+    address_key(request, trust_forwarded_for)
+}
+
+fn address_key(request: &Request, trust_forwarded_for: bool) -> String {
+    let forwarded = trust_forwarded_for
+        .then(|| {
+            request
+                .headers()
+                .get(&X_FORWARDED_FOR)
+                .and_then(|value| value.to_str().ok())
+                // Earlier entries are whatever the client sent; the last
+                // one was appended by the proxy in front of us
+                .and_then(|value| value.rsplit(',').next())
+                .map(|address| address.trim().to_string())
+        })
+        .flatten();
+
+    let address = forwarded.or_else(|| {
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string())
+    });
+
+    format!("ip:{}", address.as_deref().unwrap_or("unknown"))
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(status.limit));
+    headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(status.remaining));
+    headers.insert(RATE_LIMIT_RESET, HeaderValue::from(status.reset_secs));
+}
+
+/// CORS for `CORS_ALLOWED_ORIGINS`, or `None` when it is empty.
+pub fn cors_layer(config: &Config) -> Option<CorsLayer> {
+    if config.cors_allowed_origins.is_empty() {
+        return None;
+    }
+
+    let origins = if config
+        .cors_allowed_origins
+        .iter()
+        .any(|origin| origin == "*")
+    {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            config
+                .cors_allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+            .allow_headers([
+                AUTHORIZATION,
+                CONTENT_TYPE,
+                IF_MODIFIED_SINCE,
+                IF_NONE_MATCH,
+                API_KEY_HEADER,
+                REQUEST_ID_HEADER,
+            ])
+            .expose_headers([
+                ETAG,
+                LAST_MODIFIED,
+                RETRY_AFTER,
+                WARNING,
+                REQUEST_ID_HEADER,
+                RATE_LIMIT_LIMIT,
+                RATE_LIMIT_REMAINING,
+                RATE_LIMIT_RESET,
+            ])
+            .max_age(Duration::from_secs(3600)),
+    )
+}
+
+/// Compression for `COMPRESSION_ENABLED`: gzip, brotli or deflate, as the
+/// client's `Accept-Encoding` prefers, for responses of at least
+/// `COMPRESSION_MIN_BYTES`. Images are already compressed and skipped.
+pub fn compression_layer(config: &Config) -> Option<CompressionLayer<impl Predicate + use<>>> {
+    if !config.compression_enabled {
+        return None;
+    }
+
+    // `SizeAbove` compresses bodies larger than its argument
+    let min_bytes =
+        u16::try_from(config.compression_min_bytes.saturating_sub(1)).unwrap_or(u16::MAX);
+
+    Some(
+        CompressionLayer::new().compress_when(
+            SizeAbove::new(min_bytes)
+                .and(NotForContentType::GRPC)
+                .and(NotForContentType::IMAGES)
+                .and(NotForContentType::SSE),
+        ),
+    )
+}
+
+/// Weakens the ETag of encoded responses: a strong ETag promises the exact
+/// bytes, and those differ once compressed.
+pub async fn weaken_encoded_etag(mut response: Response) -> Response {
+    if response.headers().contains_key(CONTENT_ENCODING)
+        && let Some(etag) = response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+        && !etag.starts_with("W/")
+        && let Ok(weak) = HeaderValue::from_str(&format!("W/{}", etag))
+    {
+        response.headers_mut().insert(ETAG, weak);
+    }
+
+    response
+}
+
+/// Rewrites the bare responses of tower-http's timeout (`503`) and body limit
+/// (`413`) layers as `AppError`s, so they get the usual error body and code.
+pub async fn layer_errors(State(limit): State<usize>, response: Response) -> Response {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.contains("json"));
+
+    match response.status() {
+        _ if is_json => response,
+        StatusCode::SERVICE_UNAVAILABLE => {
+            tracing::warn!("Request timed out");
+            AppError::Timeout.into_response()
+        }
+        StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge { limit }.into_response(),
+        _ => response,
+    }
+}
@@ -0,0 +1,44 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub country_name: String,
+    pub action: String,
+    /// What triggered the change (e.g. "refresh" or "api")
+    pub source: String,
+    pub actor: Option<String>,
+    /// Per-field changes as `{ "field": { "old": ..., "new": ... } }`
+    #[schema(value_type = Object)]
+    pub changes: Value,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAuditEvent {
+    pub country_name: String,
+    pub action: AuditAction,
+    pub source: String,
+    pub actor: Option<String>,
+    pub changes: Map<String, Value>,
+}
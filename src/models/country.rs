@@ -1,16 +1,183 @@
-use serde::{Deserialize, Serialize};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize, Serializer, ser::Error, ser::SerializeMap};
+use serde_json::Value;
 use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Country {
     pub id: i32,
     pub name: String,
+    /// ISO 3166-1 alpha-2 code (e.g. "GH"), once a refresh has stored it
+    pub alpha2_code: Option<String>,
+    /// ISO 3166-1 alpha-3 code (e.g. "GHA"), once a refresh has stored it
+    pub alpha3_code: Option<String>,
     pub capital: Option<String>,
     pub region: Option<String>,
     pub population: i64,
     pub currency_code: Option<String>,
-    pub exchange_rate: Option<f64>,
-    pub estimated_gdp: Option<f64>,
+    /// Exact decimal; a JSON string when `DECIMAL_FORMAT=string`
+    #[serde(serialize_with = "crate::utils::decimal::serialize_option")]
+    #[schema(value_type = Option<f64>)]
+    pub exchange_rate: Option<BigDecimal>,
+    /// Exchange rate provider that served `exchange_rate` (e.g. "open_er_api", "ecb", "file")
+    pub rate_provider: Option<String>,
+    /// When the upstream published `exchange_rate`
+    pub rate_as_of: Option<String>,
+    /// Whether `rate_as_of` is older than `RATE_STALE_AFTER_HOURS`
+    #[serde(default)]
+    pub is_stale: bool,
+    /// Where `exchange_rate` came from: "feed" or "override"
+    pub rate_source: Option<String>,
+    /// Rate the providers reported, which differs from `exchange_rate` while an override applies
+    #[serde(serialize_with = "crate::utils::decimal::serialize_option")]
+    #[schema(value_type = Option<f64>)]
+    pub feed_exchange_rate: Option<BigDecimal>,
+    /// Exact decimal; a JSON string when `DECIMAL_FORMAT=string`
+    #[serde(serialize_with = "crate::utils::decimal::serialize_option")]
+    #[schema(value_type = Option<f64>)]
+    pub estimated_gdp: Option<BigDecimal>,
     pub flag_url: Option<String>,
     pub last_refreshed_at: String,
 }
+
+/// `Country` fields in the order they are serialized. All but `is_stale`
+/// are stored columns of the same name.
+pub const COUNTRY_FIELDS: [&str; 17] = [
+    "id",
+    "name",
+    "alpha2_code",
+    "alpha3_code",
+    "capital",
+    "region",
+    "population",
+    "currency_code",
+    "exchange_rate",
+    "rate_provider",
+    "rate_as_of",
+    "is_stale",
+    "rate_source",
+    "feed_exchange_rate",
+    "estimated_gdp",
+    "flag_url",
+    "last_refreshed_at",
+];
+
+impl Country {
+    /// Whether `key` is this country's name, case-insensitively, or its
+    /// alpha-2 or alpha-3 code.
+    pub fn matches(&self, key: &str) -> bool {
+        let key = key.trim();
+
+        self.name.to_lowercase() == key.to_lowercase()
+            || [&self.alpha2_code, &self.alpha3_code]
+                .into_iter()
+                .flatten()
+                .any(|code| code.eq_ignore_ascii_case(key))
+    }
+}
+
+/// Columns rate overrides read and rewrite.
+const RATE_COLUMNS: [&str; 5] = [
+    "currency_code",
+    "exchange_rate",
+    "rate_source",
+    "feed_exchange_rate",
+    "estimated_gdp",
+];
+
+/// The `Country` fields a response is limited to by `?fields=`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountryFields(Vec<&'static str>);
+
+impl Default for CountryFields {
+    fn default() -> Self {
+        Self(COUNTRY_FIELDS.to_vec())
+    }
+}
+
+impl CountryFields {
+    /// Parses a comma-separated list of field names, e.g.
+    /// `name,currency_code,exchange_rate`.
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let names = list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+
+        if names.is_empty() {
+            return Err("must list at least one field".to_string());
+        }
+
+        if let Some(unknown) = names.iter().find(|name| !COUNTRY_FIELDS.contains(name)) {
+            return Err(format!(
+                "unknown field {:?}; expected any of: {}",
+                unknown,
+                COUNTRY_FIELDS.join(", ")
+            ));
+        }
+
+        Ok(Self(
+            COUNTRY_FIELDS
+                .into_iter()
+                .filter(|field| names.contains(field))
+                .collect(),
+        ))
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.0.len() == COUNTRY_FIELDS.len()
+    }
+
+    /// Stored columns a read needs for these fields: the fields themselves,
+    /// the columns rate overrides and staleness are computed from when a
+    /// rate field is asked for, and `name` and `last_refreshed_at`, which
+    /// identify rows and date responses.
+    pub fn columns(&self) -> Vec<&'static str> {
+        let rates = self
+            .0
+            .iter()
+            .any(|field| RATE_COLUMNS.contains(field) || *field == "is_stale");
+
+        COUNTRY_FIELDS
+            .into_iter()
+            .filter(|column| *column != "is_stale")
+            .filter(|column| {
+                self.0.contains(column)
+                    || ["name", "last_refreshed_at"].contains(column)
+                    || (rates && RATE_COLUMNS.contains(column))
+                    || (self.0.contains(&"is_stale") && *column == "rate_as_of")
+            })
+            .collect()
+    }
+
+    /// `country` limited to these fields, serialized in the usual order.
+    pub fn project<'a>(&'a self, country: &'a Country) -> SparseCountry<'a> {
+        SparseCountry {
+            country,
+            fields: self,
+        }
+    }
+}
+
+/// A `Country` serialized with only some of its fields.
+pub struct SparseCountry<'a> {
+    country: &'a Country,
+    fields: &'a CountryFields,
+}
+
+impl Serialize for SparseCountry<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Value::Object(mut values) =
+            serde_json::to_value(self.country).map_err(S::Error::custom)?
+        else {
+            return Err(S::Error::custom("country did not serialize to an object"));
+        };
+
+        let mut map = serializer.serialize_map(Some(self.fields.0.len()))?;
+        for field in &self.fields.0 {
+            map.serialize_entry(field, &values.remove(*field).unwrap_or(Value::Null))?;
+        }
+        map.end()
+    }
+}
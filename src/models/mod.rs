@@ -1,4 +1,10 @@
+pub mod api_key;
+pub mod audit;
 pub mod country;
+pub mod quarantine;
+pub mod rate_override;
+pub mod refresh;
 pub mod requests;
 pub mod responses;
+pub mod scope;
 pub mod state;
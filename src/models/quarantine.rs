@@ -0,0 +1,26 @@
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QuarantinedRecord {
+    pub id: i64,
+    pub refresh_id: i64,
+    /// Country source the record came from
+    pub source: String,
+    pub country_name: Option<String>,
+    /// Why the record was rejected
+    pub reason: String,
+    /// The record exactly as the upstream sent it
+    #[schema(value_type = Object)]
+    pub payload: Value,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewQuarantinedRecord {
+    pub source: String,
+    pub country_name: Option<String>,
+    pub reason: String,
+    pub payload: Value,
+}
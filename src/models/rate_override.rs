@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::country::Country;
+
+/// A manually pinned exchange rate that replaces the feed rate for a currency
+/// until it expires.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RateOverride {
+    pub currency_code: String,
+    /// Exact decimal; a JSON string when `DECIMAL_FORMAT=string`
+    #[serde(serialize_with = "crate::utils::decimal::serialize")]
+    #[schema(value_type = f64)]
+    pub rate: BigDecimal,
+    pub reason: String,
+    pub expires_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct RateOverrideRequest {
+    /// Units of the currency per USD (e.g. 1450.5)
+    #[schema(value_type = Option<f64>)]
+    pub rate: Option<BigDecimal>,
+    /// Why the feed rate is being replaced (e.g. "parallel market rate")
+    pub reason: Option<String>,
+    /// RFC 3339 timestamp after which the feed rate applies again
+    pub expires_at: Option<String>,
+}
+
+/// The overrides in effect at some moment, keyed by currency code.
+#[derive(Debug, Clone, Default)]
+pub struct ActiveOverrides {
+    pub rates: HashMap<String, BigDecimal>,
+    pub expires_at: HashMap<String, DateTime<Utc>>,
+}
+
+impl ActiveOverrides {
+    /// When the first of them expires.
+    pub fn next_expiry(&self) -> Option<DateTime<Utc>> {
+        self.expires_at.values().min().copied()
+    }
+
+    /// When the first override for one of `countries`' currencies expires,
+    /// after which they read differently.
+    pub fn next_expiry_for(&self, countries: &[Country]) -> Option<DateTime<Utc>> {
+        countries
+            .iter()
+            .filter_map(|country| country.currency_code.as_ref())
+            .filter_map(|code| self.expires_at.get(code))
+            .min()
+            .copied()
+    }
+}
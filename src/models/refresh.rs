@@ -0,0 +1,98 @@
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::country::Country;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RefreshStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+impl RefreshStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefreshStatus::Running => "running",
+            RefreshStatus::Completed => "completed",
+            RefreshStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct RefreshDiff {
+    /// Countries returned by the upstream API that were not stored before
+    pub added: Vec<String>,
+    /// Stored countries no longer returned by the upstream API. A refresh
+    /// keeps their rows, so they are reported but not deleted.
+    #[serde(alias = "removed")]
+    pub missing_upstream: Vec<String>,
+    pub population_changes: Vec<PopulationChange>,
+    pub currency_changes: Vec<CurrencyChange>,
+    /// Exchange rate moves larger than the configured threshold, and rates
+    /// that appeared or disappeared
+    pub rate_changes: Vec<RateChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PopulationChange {
+    pub name: String,
+    pub old: i64,
+    pub new: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CurrencyChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RateChange {
+    pub name: String,
+    pub currency_code: Option<String>,
+    /// `null` when the country had no rate before
+    #[serde(serialize_with = "crate::utils::decimal::serialize_option")]
+    #[schema(value_type = Option<f64>)]
+    pub old: Option<BigDecimal>,
+    /// `null` when the country no longer has a rate
+    #[serde(serialize_with = "crate::utils::decimal::serialize_option")]
+    #[schema(value_type = Option<f64>)]
+    pub new: Option<BigDecimal>,
+    /// `null` when either side is `null` or the old rate was zero
+    pub change_percent: Option<f64>,
+}
+
+/// What a refresh would write, returned by `POST /countries/refresh?dry_run=true`.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct RefreshPreview {
+    /// Countries that would be created
+    pub inserted: PreviewGroup,
+    /// Stored countries whose audited fields would change
+    pub updated: PreviewGroup,
+    /// Stored countries the upstream no longer returns. These are not deleted by a refresh
+    pub missing_upstream: PreviewGroup,
+    /// Stored countries that would only get a new GDP estimate and timestamp
+    pub unchanged: usize,
+    /// Upstream records that would be quarantined
+    pub quarantined: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct PreviewGroup {
+    pub count: usize,
+    /// The first few rows, as they would be stored (or as stored now, for
+    /// `missing_upstream`)
+    pub sample: Vec<Country>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Refresh {
+    pub id: i64,
+    pub status: String,
+    pub diff: Option<RefreshDiff>,
+}
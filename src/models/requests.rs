@@ -1,14 +1,225 @@
 use serde::Deserialize;
+use serde_json::{Map, Value, json};
 use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+use crate::{
+    extract::QueryParams, models::country::CountryFields, utils::validation::is_currency_code,
+};
+
+/// Regions used by the restcountries v2 and v3 layouts.
+pub const KNOWN_REGIONS: [&str; 8] = [
+    "Africa",
+    "Americas",
+    "Antarctic",
+    "Antarctic Ocean",
+    "Asia",
+    "Europe",
+    "Oceania",
+    "Polar",
+];
+
+pub const SORT_KEYS: [&str; 2] = ["gdp_asc", "gdp_desc"];
+
+/// Whether `region` is one of `KNOWN_REGIONS`, ignoring case.
+pub fn is_known_region(region: &str) -> bool {
+    KNOWN_REGIONS
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(region))
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema)]
 pub struct CountryFilters {
-    /// Filter by region name (e.g. "Africa")
+    /// Filter by region name, case-insensitive (e.g. "Africa")
     pub region: Option<String>,
 
     /// Filter by currency code (e.g. "NGN")
     pub currency: Option<String>,
 
-    /// Sort by gdp value (e.g. "gdp_asc" or "gdp_desc")
+    /// Sort by gdp value ("gdp_asc" or "gdp_desc", the default)
     pub sort: Option<String>,
+
+    /// Return data as it was stored at this RFC 3339 timestamp (e.g. "2026-03-01T00:00:00Z")
+    pub as_of: Option<String>,
+
+    /// Only return these comma-separated `Country` fields (e.g. "name,currency_code,exchange_rate")
+    pub fields: Option<String>,
+}
+
+impl CountryFilters {
+    /// The `fields` selection, or every field. Only valid after `validate`.
+    pub fn fields(&self) -> CountryFields {
+        selected_fields(self.fields.as_deref())
+    }
+}
+
+impl QueryParams for CountryFilters {
+    const FIELDS: &'static [&'static str] = &["region", "currency", "sort", "as_of", "fields"];
+
+    fn validate(&self) -> Map<String, Value> {
+        let mut errors = Map::new();
+
+        if let Some(region) = &self.region
+            && !is_known_region(region)
+        {
+            errors.insert(
+                "region".to_string(),
+                json!(format!("must be one of: {}", KNOWN_REGIONS.join(", "))),
+            );
+        }
+
+        if let Some(currency) = &self.currency
+            && !is_currency_code(currency)
+        {
+            errors.insert(
+                "currency".to_string(),
+                json!("must be a 3-letter uppercase currency code"),
+            );
+        }
+
+        if let Some(sort) = &self.sort
+            && !SORT_KEYS.contains(&sort.as_str())
+        {
+            errors.insert(
+                "sort".to_string(),
+                json!(format!("must be one of: {}", SORT_KEYS.join(", "))),
+            );
+        }
+
+        validate_fields(self.fields.as_deref(), &mut errors);
+
+        errors
+    }
+}
+
+fn selected_fields(fields: Option<&str>) -> CountryFields {
+    fields
+        .and_then(|fields| CountryFields::parse(fields).ok())
+        .unwrap_or_default()
+}
+
+fn validate_fields(fields: Option<&str>, errors: &mut Map<String, Value>) {
+    if let Some(fields) = fields
+        && let Err(e) = CountryFields::parse(fields)
+    {
+        errors.insert("fields".to_string(), json!(e));
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema)]
+pub struct AuditFilters {
+    /// Filter by country name (e.g. "Ghana")
+    pub country: Option<String>,
+
+    /// Only return events at or after this RFC 3339 timestamp (e.g. "2026-03-01T00:00:00Z")
+    pub since: Option<String>,
+}
+
+impl QueryParams for AuditFilters {
+    const FIELDS: &'static [&'static str] = &["country", "since"];
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema)]
+pub struct AsOfQuery {
+    /// Return data as it was stored at this RFC 3339 timestamp (e.g. "2026-03-01T00:00:00Z")
+    pub as_of: Option<String>,
+}
+
+impl QueryParams for AsOfQuery {
+    const FIELDS: &'static [&'static str] = &["as_of"];
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema)]
+pub struct CountryQuery {
+    /// Return data as it was stored at this RFC 3339 timestamp (e.g. "2026-03-01T00:00:00Z")
+    pub as_of: Option<String>,
+
+    /// Only return these comma-separated `Country` fields (e.g. "name,currency_code,exchange_rate")
+    pub fields: Option<String>,
+}
+
+impl CountryQuery {
+    /// The `fields` selection, or every field. Only valid after `validate`.
+    pub fn fields(&self) -> CountryFields {
+        selected_fields(self.fields.as_deref())
+    }
+}
+
+impl QueryParams for CountryQuery {
+    const FIELDS: &'static [&'static str] = &["as_of", "fields"];
+
+    fn validate(&self) -> Map<String, Value> {
+        let mut errors = Map::new();
+        validate_fields(self.fields.as_deref(), &mut errors);
+        errors
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema)]
+pub struct RefreshQuery {
+    /// Preview what the refresh would change without storing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl QueryParams for RefreshQuery {
+    const FIELDS: &'static [&'static str] = &["dry_run"];
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshScope {
+    /// Reapply fresh exchange rates to the stored countries without fetching countries
+    RatesOnly,
+    /// Refetch country metadata but keep the stored exchange rates
+    CountriesOnly,
+    #[default]
+    All,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefreshRequest {
+    #[serde(default)]
+    pub scope: RefreshScope,
+
+    /// Only refresh these countries (case-insensitive names, e.g. ["Ghana"])
+    #[serde(default)]
+    pub countries: Vec<String>,
+
+    /// Only refresh countries in these regions (case-insensitive, one of
+    /// `KNOWN_REGIONS`, e.g. ["Africa"])
+    #[serde(default)]
+    pub regions: Vec<String>,
+}
+
+impl RefreshRequest {
+    /// Whether the refresh is limited to some countries or regions.
+    pub fn is_selective(&self) -> bool {
+        !self.countries.is_empty() || !self.regions.is_empty()
+    }
+
+    /// Whether a country falls inside the selection. Everything does when no
+    /// countries or regions were given.
+    pub fn selects(&self, name: &str, region: Option<&str>) -> bool {
+        if !self.is_selective() {
+            return true;
+        }
+
+        self.countries
+            .iter()
+            .any(|selected| selected.eq_ignore_ascii_case(name))
+            || region.is_some_and(|region| {
+                self.regions
+                    .iter()
+                    .any(|selected| selected.eq_ignore_ascii_case(region))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct BatchCountriesRequest {
+    /// Country names (case-insensitive) or ISO 3166-1 alpha-2 or alpha-3
+    /// codes, mixed freely (e.g. ["Ghana", "NG", "KEN"])
+    #[serde(default)]
+    pub countries: Vec<String>,
 }
@@ -1,12 +1,19 @@
 use std::collections::HashMap;
 
+use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use utoipa::ToSchema;
 
+use crate::{db::cache::CacheStats, models::country::Country, utils::resilience::UpstreamStatus};
+
 #[derive(Debug, Deserialize)]
 pub struct CountryResponse {
     pub name: String,
+    #[serde(rename = "alpha2Code", default)]
+    pub alpha2_code: Option<String>,
+    #[serde(rename = "alpha3Code", default)]
+    pub alpha3_code: Option<String>,
     pub capital: Option<String>,
     pub region: Option<String>,
     pub population: i64,
@@ -23,41 +30,126 @@ pub struct Currency {
     pub symbol: Option<String>,
 }
 
+/// A country in the restcountries v3.1 layout.
+#[derive(Debug, Deserialize)]
+pub struct CountryResponseV3 {
+    pub name: CountryNameV3,
+    pub cca2: Option<String>,
+    pub cca3: Option<String>,
+    #[serde(default)]
+    pub capital: Vec<String>,
+    pub region: Option<String>,
+    pub population: i64,
+    /// In the order the upstream listed them
+    #[serde(default)]
+    pub currencies: serde_json::Map<String, Value>,
+    pub flags: Option<FlagsV3>,
+    #[serde(default)]
+    pub independent: bool,
+}
+
 #[derive(Debug, Deserialize)]
+pub struct CountryNameV3 {
+    pub common: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FlagsV3 {
+    pub svg: Option<String>,
+    pub png: Option<String>,
+}
+
+impl From<CountryResponseV3> for CountryResponse {
+    fn from(country: CountryResponseV3) -> Self {
+        // v3.1 keys currencies by code; `preserve_order` keeps the upstream's
+        // order, so the first listed stays the primary currency
+        let currencies = country
+            .currencies
+            .into_iter()
+            .map(|(code, details)| Currency {
+                code: Some(code),
+                name: details["name"].as_str().map(str::to_string),
+                symbol: details["symbol"].as_str().map(str::to_string),
+            })
+            .collect();
+
+        Self {
+            name: country.name.common,
+            alpha2_code: country.cca2,
+            alpha3_code: country.cca3,
+            capital: country.capital.into_iter().next(),
+            region: country.region,
+            population: country.population,
+            currencies: Some(currencies),
+            flag: country.flags.and_then(|flags| flags.svg.or(flags.png)),
+            independent: country.independent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct ExchangeRateResponse {
-    pub rates: HashMap<String, f64>,
+    pub rates: HashMap<String, BigDecimal>,
+    /// When the upstream published these rates, if it says
+    #[serde(default)]
+    pub time_last_update_unix: Option<i64>,
+    /// When the upstream publishes its next update, if it says
+    #[serde(default)]
+    pub time_next_update_unix: Option<i64>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct RefreshResponse {
     pub message: String,
+    pub refresh_id: i64,
+    /// Upstream records that passed validation
+    pub accepted: usize,
+    /// Upstream records rejected and stored in quarantine
+    pub quarantined: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchCountriesResponse {
+    /// Countries found, in the order they were first asked for
+    pub countries: Vec<Country>,
+    /// Requested names or codes that matched no country, as sent
+    pub missing: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct StatusResponse {
     pub total_countries: i64,
     pub last_refreshed_at: Option<String>,
+    pub schema_version: Option<i64>,
+    pub upstreams: Vec<UpstreamStatus>,
+    /// Read cache counters, or null when `COUNTRY_CACHE_TTL_SECS=0`
+    pub country_cache: Option<CacheStats>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ApiError {
     pub error: String,
+    /// Stable machine-readable error code (e.g. "not_found", "validation_failed")
+    pub code: String,
+    /// Matches the `X-Request-Id` response header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<Value>,
 }
 
-impl ApiError {
-    pub fn new(error: impl Into<String>) -> Self {
-        Self {
-            error: error.into(),
-            details: None,
-        }
-    }
-
-    pub fn with_details(error: impl Into<String>, details: Value) -> Self {
-        Self {
-            error: error.into(),
-            details: Some(details),
-        }
-    }
+/// RFC 7807 error body, sent as `application/problem+json` when
+/// `ERROR_FORMAT=problem` or the client asks for it in `Accept`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
 }
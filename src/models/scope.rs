@@ -0,0 +1,53 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::anyhow;
+
+/// A permission checked per route. API keys get theirs from their role;
+/// bearer tokens list them in the `scope` (or `scp`) claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Every `GET` endpoint except the docs, which stay public, and
+    /// `POST /countries/batch`
+    CountriesRead,
+    /// `POST /countries/refresh`
+    CountriesRefresh,
+    /// `DELETE /countries/{name}`
+    CountriesWrite,
+    /// `PUT` and `DELETE /currencies/{code}/override`
+    RatesWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [
+        Scope::CountriesRead,
+        Scope::CountriesRefresh,
+        Scope::CountriesWrite,
+        Scope::RatesWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::CountriesRead => "countries:read",
+            Scope::CountriesRefresh => "countries:refresh",
+            Scope::CountriesWrite => "countries:write",
+            Scope::RatesWrite => "rates:write",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == value)
+            .ok_or_else(|| anyhow!("unknown scope {:?}", value))
+    }
+}
@@ -1,7 +1,31 @@
-use crate::{db::repositories::CountryRepository, utils::config::Config};
+use crate::{
+    db::repositories::{
+        ApiKeyRepository, AuditRepository, CountryRepository, QuarantineRepository,
+        RateOverrideRepository, RefreshRepository,
+    },
+    jwt::JwtVerifier,
+    utils::{
+        config::Config, http::HttpClients, rate_limit::RateLimits, resilience::CircuitBreakers,
+        upstream_cache::UpstreamCache,
+    },
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub repository: CountryRepository,
+    pub audit: AuditRepository,
+    pub refreshes: RefreshRepository,
+    pub quarantine: QuarantineRepository,
+    pub overrides: RateOverrideRepository,
+    pub api_keys: ApiKeyRepository,
+    /// Set when a JWKS is configured, so bearer tokens are accepted
+    pub jwt: Option<JwtVerifier>,
     pub config: Config,
+    /// Latest migration version applied to the database at startup
+    pub schema_version: Option<i64>,
+    pub http: HttpClients,
+    pub breakers: CircuitBreakers,
+    pub upstream_cache: UpstreamCache,
+    /// Per-client buckets, shared by every clone of the state
+    pub rate_limits: RateLimits,
 }
@@ -0,0 +1,40 @@
+use axum::{Json, extract::State, response::IntoResponse};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+
+use crate::{
+    error::AppError,
+    extract::ValidatedQuery,
+    models::{audit::AuditEvent, requests::AuditFilters, responses::ApiError, state::AppState},
+};
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    params(AuditFilters),
+    responses(
+        (status = 200, description = "Audit events matching filters, oldest first", body = [AuditEvent]),
+        (status = 400, description = "Invalid query parameters", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "Audit"
+)]
+pub async fn get_audit_events(
+    State(state): State<AppState>,
+    ValidatedQuery(filters): ValidatedQuery<AuditFilters>,
+) -> Result<impl IntoResponse, AppError> {
+    let since = match filters.since.as_deref().map(str::parse::<DateTime<Utc>>) {
+        Some(Ok(since)) => Some(since),
+        Some(Err(_)) => {
+            return Err(AppError::validation(
+                "since",
+                "must be an RFC 3339 timestamp",
+            ));
+        }
+        None => None,
+    };
+
+    let events = state.audit.list(filters.country.as_deref(), since).await?;
+
+    Ok((StatusCode::OK, Json(events)))
+}
@@ -1,94 +1,297 @@
+use std::collections::HashMap;
+
 use axum::{
-    Json,
+    Extension, Json,
     body::Body,
-    extract::{Path, Query, State},
-    http::Response,
+    extract::{Path, State},
+    http::{
+        HeaderMap, HeaderValue, Response,
+        header::{CONTENT_TYPE, WARNING},
+    },
     response::IntoResponse,
 };
-use chrono::Utc;
+use chrono::{DateTime, Duration, Utc};
 use reqwest::StatusCode;
-use serde_json::json;
+use serde::Serialize;
 
 use crate::{
+    auth::Principal,
+    error::AppError,
+    extract::{ValidatedJson, ValidatedQuery},
     models::{
+        audit::{AuditAction, NewAuditEvent},
         country::Country,
-        requests::CountryFilters,
-        responses::{ApiError, RefreshResponse, StatusResponse},
+        quarantine::NewQuarantinedRecord,
+        refresh::RefreshPreview,
+        requests::{
+            AsOfQuery, BatchCountriesRequest, CountryFilters, CountryQuery, KNOWN_REGIONS,
+            RefreshQuery, RefreshRequest, RefreshScope, is_known_region,
+        },
+        responses::{
+            ApiError, BatchCountriesResponse, CountryResponse, RefreshResponse, StatusResponse,
+        },
         state::AppState,
     },
     utils::{
-        clients::{CountriesApiClient, ExchangeApiClient},
-        tasks::{generate_image_task, refresh_countries_task},
+        countries::{
+            apply_rate_overrides, country_changes, flag_stale_rates, preview_refresh,
+            primary_currency_codes, stored_country_response,
+        },
+        http_cache::{ResponseValidators, cache_control, not_modified, with_cache_headers},
+        rates::{ExchangeRates, RateProviderChain},
+        sources::country_source_from_config,
+        tasks::{UpstreamData, build_countries, generate_image_task, refresh_countries_task},
+        upstream_cache::UpstreamCache,
     },
 };
 
+async fn load_stored_countries(state: &AppState) -> Result<Vec<Country>, AppError> {
+    Ok(state
+        .repository
+        .filter(&CountryFilters::default(), None)
+        .await?)
+}
+
+/// Fetches countries from the configured source, falling back to the stored
+/// rows when it reports nothing changed.
+async fn fetch_countries(
+    state: &AppState,
+    cache: &UpstreamCache,
+) -> Result<(Vec<CountryResponse>, Vec<NewQuarantinedRecord>, bool), AppError> {
+    let country_source =
+        country_source_from_config(&state.config, &state.http, &state.breakers, cache);
+
+    match country_source.fetch_countries().await {
+        Ok(Some(parsed)) if parsed.countries.is_empty() && !parsed.rejected.is_empty() => {
+            tracing::error!(
+                "Country source {} returned no valid records ({} rejected)",
+                country_source.name(),
+                parsed.rejected.len()
+            );
+            Err(AppError::UpstreamUnavailable(format!(
+                "{} country source returned no valid records",
+                country_source.name()
+            )))
+        }
+        Ok(Some(parsed)) => Ok((parsed.countries, parsed.rejected, true)),
+        Ok(None) => {
+            let stored = load_stored_countries(state).await?;
+            Ok((
+                stored.iter().map(stored_country_response).collect(),
+                Vec::new(),
+                false,
+            ))
+        }
+        Err(e) => {
+            tracing::error!(
+                "Country source {} unavailable: {:?}",
+                country_source.name(),
+                e
+            );
+            Err(AppError::UpstreamUnavailable(format!(
+                "Could not fetch data from {} country source",
+                country_source.name()
+            )))
+        }
+    }
+}
+
+async fn fetch_upstream(
+    state: &AppState,
+    cache: &UpstreamCache,
+    request: &RefreshRequest,
+) -> Result<UpstreamData, AppError> {
+    let stored = match request.scope {
+        RefreshScope::All => Vec::new(),
+        RefreshScope::RatesOnly | RefreshScope::CountriesOnly => {
+            load_stored_countries(state).await?
+        }
+    };
+
+    let (mut countries, rejected, countries_modified) = match request.scope {
+        RefreshScope::RatesOnly => (
+            stored.iter().map(stored_country_response).collect(),
+            Vec::new(),
+            false,
+        ),
+        RefreshScope::CountriesOnly | RefreshScope::All => fetch_countries(state, cache).await?,
+    };
+
+    countries.retain(|country| request.selects(&country.name, country.region.as_deref()));
+
+    if countries.is_empty() && request.is_selective() {
+        return Err(AppError::validation(
+            "countries",
+            "no countries match the given names or regions",
+        ));
+    }
+
+    let mut rates = match request.scope {
+        RefreshScope::CountriesOnly => ExchangeRates::from_stored(&stored),
+        RefreshScope::RatesOnly | RefreshScope::All => {
+            let rate_providers =
+                RateProviderChain::from_config(&state.config, &state.http, &state.breakers, cache);
+            let wanted_currencies = primary_currency_codes(&countries);
+
+            match rate_providers.fetch_rates(&wanted_currencies).await {
+                Ok(rates) => rates,
+                Err(e) => {
+                    tracing::error!("Exchange rates API unavailable: {:?}", e);
+                    return Err(AppError::UpstreamUnavailable(
+                        "Could not fetch data from any exchange rate provider".to_string(),
+                    ));
+                }
+            }
+        }
+    };
+
+    rates.overrides = state.overrides.active_rates(Utc::now()).await?;
+
+    Ok(UpstreamData {
+        countries,
+        rejected,
+        countries_modified,
+        rates,
+    })
+}
+
+/// Number of example rows returned per group by a dry-run refresh.
+const PREVIEW_SAMPLE_SIZE: usize = 5;
+
 #[utoipa::path(
     post,
     path = "/countries/refresh",
+    params(RefreshQuery),
+    request_body(content = Option<RefreshRequest>, description = "Limit the refresh to rates or countries, or to some countries or regions"),
     responses(
-        (status = 200, description = "Refresh started in background"),
+        (status = 200, description = "Refresh started in background, or the dry-run preview", body = RefreshPreview),
+        (status = 400, description = "No countries match the selection", body = ApiError),
         (status = 503, description = "Service Unavailable - External data source unavailable", body = ApiError),
     ),
     tag = "Countries"
 )]
-pub async fn refresh_countries(State(state): State<AppState>) -> impl IntoResponse {
-    let countries_client = CountriesApiClient::new(state.config.rest_countries_api);
-    let exchange_client = ExchangeApiClient::new(state.config.exchange_rates_api);
-
-    let countries_data = countries_client.fetch_all_countries().await;
-    if let Err(e) = countries_data {
-        tracing::error!("Countries API unavailable: {:?}", e);
-        return (
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(ApiError::with_details(
-                "External data source unavailable".to_string(),
-                "Could not fetch data from restcountries API".into(),
-            )),
-        )
-            .into_response();
+pub async fn refresh_countries(
+    State(state): State<AppState>,
+    ValidatedQuery(query): ValidatedQuery<RefreshQuery>,
+    principal: Option<Extension<Principal>>,
+    request: Option<ValidatedJson<RefreshRequest>>,
+) -> Result<Response<Body>, AppError> {
+    let request = request
+        .map(|ValidatedJson(request)| request)
+        .unwrap_or_default();
+
+    if let Some(region) = request
+        .regions
+        .iter()
+        .find(|region| !is_known_region(region))
+    {
+        return Err(AppError::validation(
+            "regions",
+            &format!(
+                "unknown region {:?}; must be one of: {}",
+                region,
+                KNOWN_REGIONS.join(", ")
+            ),
+        ));
     }
 
-    let exchange_rate_data = exchange_client.fetch_rates().await;
-    if let Err(e) = exchange_rate_data {
-        tracing::error!("Exchange rates API unavailable: {:?}", e);
-        return (
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(ApiError::with_details(
-                "External data source unavailable".to_string(),
-                "Could not fetch data from exchange rates API".into(),
-            )),
-        )
-            .into_response();
+    if query.dry_run {
+        return preview_refresh_countries(&state, &request).await;
     }
 
-    tokio::spawn(async move {
-        let timestamp = Utc::now();
+    // Validators stored by a selective refresh would make the next full one
+    // see `304` for countries it never stored, so those use a throwaway cache
+    let cache = if request.is_selective() {
+        UpstreamCache::new()
+    } else {
+        state.upstream_cache.clone()
+    };
 
+    let data = fetch_upstream(&state, &cache, &request).await?;
+
+    let timestamp = Utc::now();
+    let refresh_id = state.refreshes.start(timestamp).await?;
+
+    if let Err(e) = state.quarantine.record(refresh_id, &data.rejected).await {
+        tracing::error!("Failed to store quarantined records: {:?}", e);
+    }
+
+    let accepted = data.countries.len();
+    let quarantined = data.rejected.len();
+    let actor = principal.map(|Extension(principal)| principal.subject);
+
+    tokio::spawn(async move {
         match refresh_countries_task(
             state.repository.clone(),
-            countries_data.unwrap(),
-            exchange_rate_data.unwrap(),
+            state.audit.clone(),
+            data,
+            request,
             timestamp,
+            state.config.rate_change_threshold,
+            actor,
         )
         .await
         {
-            Ok(_) => tracing::info!("Refresh completed successfully"),
-            Err(e) => tracing::error!("Refresh failed: {:?}", e),
+            Ok(diff) => {
+                tracing::info!("Refresh completed successfully");
+
+                if let Err(e) = state.refreshes.complete(refresh_id, &diff).await {
+                    tracing::error!("Failed to store refresh diff: {:?}", e);
+                }
+            }
+            Err(e) => {
+                tracing::error!("Refresh failed: {:?}", e);
+
+                // Make the next refresh download everything again rather than
+                // trusting validators for data that never got stored
+                state.upstream_cache.clear();
+
+                if let Err(e) = state.refreshes.fail(refresh_id, &e.to_string()).await {
+                    tracing::error!("Failed to record refresh failure: {:?}", e);
+                }
+            }
         }
 
-        match generate_image_task(state.repository.clone(), timestamp).await {
+        match generate_image_task(state.repository.clone(), &state.http.default, timestamp).await {
             Ok(_) => tracing::info!("Image generated successfully"),
             Err(e) => tracing::error!("Failed to generate summary image: {:?}", e),
         }
     });
 
-    (
+    Ok((
         StatusCode::OK,
         Json(RefreshResponse {
             message: "Refresh started in background".to_string(),
+            refresh_id,
+            accepted,
+            quarantined,
         }),
     )
-        .into_response()
+        .into_response())
+}
+
+/// Runs the fetch and processing steps of a refresh and reports what would
+/// change, without storing anything or regenerating the image.
+async fn preview_refresh_countries(
+    state: &AppState,
+    request: &RefreshRequest,
+) -> Result<Response<Body>, AppError> {
+    // A throwaway cache, so the preview neither relies on nor consumes the
+    // validators the next real refresh will send
+    let data = fetch_upstream(state, &UpstreamCache::new(), request).await?;
+
+    let existing = load_stored_countries(state)
+        .await?
+        .into_iter()
+        .filter(|country| request.selects(&country.name, country.region.as_deref()))
+        .map(|country| (country.name.to_lowercase(), country))
+        .collect::<HashMap<String, Country>>();
+
+    let countries = build_countries(data.countries, &data.rates, Utc::now());
+    let mut preview = preview_refresh(&existing, &countries, PREVIEW_SAMPLE_SIZE);
+    preview.quarantined = data.rejected.len();
+
+    Ok((StatusCode::OK, Json(preview)).into_response())
 }
 
 #[utoipa::path(
@@ -96,36 +299,70 @@ pub async fn refresh_countries(State(state): State<AppState>) -> impl IntoRespon
     path = "/countries",
     params(CountryFilters),
     responses(
-        (status = 200, description = "List of countries matching filters", body = [Country]),
+        (status = 200, description = "List of countries matching filters, with only the `fields` asked for", body = [Country]),
+        (status = 304, description = "Not modified since the `If-None-Match` or `If-Modified-Since` version"),
+        (status = 400, description = "Invalid query parameters", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "Countries"
 )]
 pub async fn get_countries(
     State(state): State<AppState>,
-    Query(filters): Query<CountryFilters>,
-) -> impl IntoResponse {
-    match state.repository.filter(&filters).await {
-        Ok(countries) => (StatusCode::OK, Json(countries)).into_response(),
-        Err(e) => {
-            tracing::error!("Failed to fetch countries: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::new("Internal server error")),
-            )
-                .into_response()
-        }
+    ValidatedQuery(filters): ValidatedQuery<CountryFilters>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    let as_of = parse_as_of(filters.as_of.as_deref())?;
+    let fields = filters.fields();
+
+    let countries = state
+        .repository
+        .filter_fields(&filters, as_of, &fields)
+        .await?;
+    let (mut countries, overrides_expire_at) =
+        with_current_overrides(&state, countries, as_of).await?;
+
+    let stale = flag_stale_rates(
+        &mut countries,
+        Duration::hours(state.config.rate_stale_after_hours as i64),
+        as_of.unwrap_or_else(Utc::now),
+    );
+
+    if fields.is_all() {
+        return cached_json(
+            &state,
+            &headers,
+            &countries,
+            &countries,
+            stale,
+            overrides_expire_at,
+        );
     }
+
+    let sparse = countries
+        .iter()
+        .map(|country| fields.project(country))
+        .collect::<Vec<_>>();
+    cached_json(
+        &state,
+        &headers,
+        &sparse,
+        &countries,
+        stale,
+        overrides_expire_at,
+    )
 }
 
 #[utoipa::path(
     get,
     path = "/countries/{name}",
     params(
-        ("name" = String, Path, description = "The name of country to retrieve")
+        ("name" = String, Path, description = "The name of country to retrieve"),
+        CountryQuery
     ),
     responses(
-        (status = 200, description = "Country found", body = Country),
+        (status = 200, description = "Country found, with only the `fields` asked for", body = Country),
+        (status = 304, description = "Not modified since the `If-None-Match` or `If-Modified-Since` version"),
+        (status = 400, description = "Invalid query parameters", body = ApiError),
         (status = 404, description = "Country not found", body = Country),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
@@ -134,23 +371,116 @@ pub async fn get_countries(
 pub async fn get_country(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> impl IntoResponse {
-    match state.repository.get_by_name(&name).await {
-        Ok(Some(country)) => (StatusCode::OK, Json(country)).into_response(),
-        Ok(None) => (
-            StatusCode::NOT_FOUND,
-            Json(ApiError::new("Country not found")),
-        )
-            .into_response(),
-        Err(e) => {
-            tracing::error!("Failed to fetch country: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::new("Internal server error")),
-            )
-                .into_response()
+    ValidatedQuery(query): ValidatedQuery<CountryQuery>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    let as_of = parse_as_of(query.as_of.as_deref())?;
+    let fields = query.fields();
+
+    let country = state
+        .repository
+        .get_by_name_fields(&name, as_of, &fields)
+        .await?
+        .ok_or_else(country_not_found)?;
+
+    let (mut countries, overrides_expire_at) =
+        with_current_overrides(&state, vec![country], as_of).await?;
+    let stale = flag_stale_rates(
+        &mut countries,
+        Duration::hours(state.config.rate_stale_after_hours as i64),
+        as_of.unwrap_or_else(Utc::now),
+    );
+
+    if fields.is_all() {
+        return cached_json(
+            &state,
+            &headers,
+            &countries[0],
+            &countries,
+            stale,
+            overrides_expire_at,
+        );
+    }
+
+    cached_json(
+        &state,
+        &headers,
+        &fields.project(&countries[0]),
+        &countries,
+        stale,
+        overrides_expire_at,
+    )
+}
+
+/// Most names or codes one `POST /countries/batch` may ask for.
+pub const MAX_BATCH_SIZE: usize = 100;
+
+#[utoipa::path(
+    post,
+    path = "/countries/batch",
+    request_body = BatchCountriesRequest,
+    responses(
+        (status = 200, description = "Countries found, and the names or codes that matched none", body = BatchCountriesResponse),
+        (status = 400, description = "No names or codes, blank ones, or too many", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "Countries"
+)]
+pub async fn get_countries_batch(
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<BatchCountriesRequest>,
+) -> Result<Response<Body>, AppError> {
+    let keys = request
+        .countries
+        .iter()
+        .map(|key| key.trim().to_string())
+        .collect::<Vec<_>>();
+
+    if keys.is_empty() {
+        return Err(AppError::validation(
+            "countries",
+            "must list at least one name or code",
+        ));
+    }
+    if keys.len() > MAX_BATCH_SIZE {
+        return Err(AppError::validation(
+            "countries",
+            &format!("must list at most {} names or codes", MAX_BATCH_SIZE),
+        ));
+    }
+    if keys.iter().any(String::is_empty) {
+        return Err(AppError::validation(
+            "countries",
+            "must not contain blank names or codes",
+        ));
+    }
+
+    let found = state.repository.get_by_names_or_codes(&keys).await?;
+    let (mut found, _) = with_current_overrides(&state, found, None).await?;
+    let stale = flag_stale_rates(
+        &mut found,
+        Duration::hours(state.config.rate_stale_after_hours as i64),
+        Utc::now(),
+    );
+
+    // A country asked for by name and by code is returned once
+    let mut countries = Vec::<Country>::new();
+    let mut missing = Vec::new();
+    for key in keys {
+        match found.iter().find(|country| country.matches(&key)) {
+            Some(country) if !countries.iter().any(|listed| listed.name == country.name) => {
+                countries.push(country.clone())
+            }
+            Some(_) => {}
+            None if !missing.contains(&key) => missing.push(key),
+            None => {}
         }
     }
+
+    Ok(with_stale_warning(
+        Json(BatchCountriesResponse { countries, missing }).into_response(),
+        stale,
+    ))
 }
 
 #[utoipa::path(
@@ -169,55 +499,68 @@ pub async fn get_country(
 pub async fn delete_country(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> impl IntoResponse {
-    match state.repository.delete_by_name(&name).await {
-        Ok(true) => StatusCode::NO_CONTENT.into_response(),
-        Ok(false) => (
-            StatusCode::NOT_FOUND,
-            Json(ApiError::new("Country not found")),
-        )
-            .into_response(),
-        Err(e) => {
-            tracing::error!("Failed to delete country: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::new("Internal server error")),
-            )
-                .into_response()
-        }
+    principal: Option<Extension<Principal>>,
+) -> Result<impl IntoResponse, AppError> {
+    let country = state
+        .repository
+        .get_by_name(&name)
+        .await?
+        .ok_or_else(country_not_found)?;
+
+    let event = NewAuditEvent {
+        country_name: country.name.clone(),
+        action: AuditAction::Delete,
+        source: "api".to_string(),
+        actor: principal.map(|Extension(principal)| principal.subject),
+        changes: country_changes(Some(&country), None),
+    };
+
+    if !state.repository.delete_by_name(&name, &[event]).await? {
+        return Err(country_not_found());
     }
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[utoipa::path(
     get,
     path = "/status",
+    params(AsOfQuery),
     responses(
-        (status = 200)
+        (status = 200),
+        (status = 400, description = "Invalid query parameters", body = ApiError)
     ),
     tag = "Status"
 )]
-pub async fn get_status(State(state): State<AppState>) -> impl IntoResponse {
-    let count = state.repository.count().await.unwrap_or(0);
-    let last_refresh = state.repository.get_last_refresh_time().await.ok();
-
-    match last_refresh {
-        Some(timestamp) => (
-            StatusCode::OK,
-            Json(StatusResponse {
-                total_countries: count,
-                last_refreshed_at: timestamp,
-            }),
-        )
-            .into_response(),
+pub async fn get_status(
+    State(state): State<AppState>,
+    ValidatedQuery(as_of): ValidatedQuery<AsOfQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let (count, last_refresh) = match parse_as_of(as_of.as_of.as_deref())? {
+        Some(as_of) => (
+            state.repository.count_as_of(as_of).await.unwrap_or(0),
+            state
+                .repository
+                .get_last_refresh_time_as_of(as_of)
+                .await
+                .ok(),
+        ),
         None => (
-            StatusCode::OK,
-            Json(json!({
-                "total_countries": count,
-                "last_refreshed_at": null
-            })),
-        )
-            .into_response(),
-    }
+            state.repository.count().await.unwrap_or(0),
+            state.repository.get_last_refresh_time().await.ok(),
+        ),
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(StatusResponse {
+            total_countries: count,
+            last_refreshed_at: last_refresh.flatten(),
+            schema_version: state.schema_version,
+            upstreams: state.breakers.statuses(),
+            country_cache: state.repository.cache_stats(),
+        }),
+    ))
 }
 
 #[utoipa::path(
@@ -225,36 +568,126 @@ pub async fn get_status(State(state): State<AppState>) -> impl IntoResponse {
     path = "/countries/image",
     responses(
         (status = 200, description = "Summary image successfully retrieved", content_type = "image/png"),
+        (status = 304, description = "Not modified since the `If-None-Match` or `If-Modified-Since` version"),
         (status = 404, description = "Summary image not found", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "Summary"
 )]
-pub async fn get_summary_image() -> impl IntoResponse {
+pub async fn get_summary_image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
     let image_path = "cache/summary.png";
 
-    if !std::path::Path::new(image_path).exists() {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(ApiError::new("Summary image not found")),
-        )
-            .into_response();
+    let Ok(metadata) = tokio::fs::metadata(image_path).await else {
+        return Err(AppError::NotFound("Summary image not found".to_string()));
+    };
+
+    // Tagged by size and modification time, so a current client copy is
+    // answered without reading the file
+    let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+    let validators = ResponseValidators {
+        etag: format!(
+            "\"{:x}-{:x}\"",
+            metadata.len(),
+            modified.map(|at| at.timestamp_micros()).unwrap_or_default()
+        ),
+        last_modified: modified,
+    };
+    let cache_control = cache_control(&state.config, modified, None);
+
+    if validators.matches(&headers) {
+        return Ok(not_modified(&validators, cache_control));
     }
 
-    match tokio::fs::read(image_path).await {
-        Ok(contents) => Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "image/png")
-            .body(Body::from(contents))
-            .unwrap()
-            .into_response(),
-        Err(e) => {
-            tracing::error!("Failed to read image: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::new("Failed to serve image")),
-            )
-                .into_response()
-        }
+    let contents = tokio::fs::read(image_path)
+        .await
+        .map_err(|e| anyhow::Error::new(e).context("Failed to read summary image"))?;
+
+    Ok(with_cache_headers(
+        ([(CONTENT_TYPE, "image/png")], contents).into_response(),
+        &validators,
+        cache_control,
+    ))
+}
+
+fn parse_as_of(as_of: Option<&str>) -> Result<Option<DateTime<Utc>>, AppError> {
+    match as_of.map(str::parse::<DateTime<Utc>>) {
+        Some(Ok(as_of)) => Ok(Some(as_of)),
+        Some(Err(_)) => Err(AppError::validation(
+            "as_of",
+            "must be an RFC 3339 timestamp",
+        )),
+        None => Ok(None),
     }
 }
+
+fn country_not_found() -> AppError {
+    AppError::NotFound("Country not found".to_string())
+}
+
+/// Serializes `body` with an ETag and freshness derived from `countries`,
+/// answering `304` instead when the client already has this version.
+/// `overrides_expire_at` is when a rate override applied to them expires.
+fn cached_json<T: Serialize>(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &T,
+    countries: &[Country],
+    stale: bool,
+    overrides_expire_at: Option<DateTime<Utc>>,
+) -> Result<Response<Body>, AppError> {
+    let last_refreshed = countries
+        .iter()
+        .filter_map(|country| country.last_refreshed_at.parse::<DateTime<Utc>>().ok())
+        .max();
+
+    // No Last-Modified: deletes and expiring overrides change responses
+    // without moving any returned row's timestamp, so only the ETag is reliable
+    let body = serde_json::to_vec(body).map_err(anyhow::Error::from)?;
+    let validators = ResponseValidators::from_body(&body, None);
+    let cache_control = cache_control(&state.config, last_refreshed, overrides_expire_at);
+
+    if validators.matches(headers) {
+        return Ok(not_modified(&validators, cache_control));
+    }
+
+    let response = ([(CONTENT_TYPE, "application/json")], body).into_response();
+
+    Ok(with_stale_warning(
+        with_cache_headers(response, &validators, cache_control),
+        stale,
+    ))
+}
+
+/// Marks responses carrying exchange rates older than `RATE_STALE_AFTER_HOURS`
+/// with `Warning: 110` ("Response is Stale").
+fn with_stale_warning(mut response: Response<Body>, stale: bool) -> Response<Body> {
+    if stale {
+        response.headers_mut().insert(
+            WARNING,
+            HeaderValue::from_static("110 - \"Exchange rates are stale\""),
+        );
+    }
+
+    response
+}
+
+/// Applies the rate overrides active now to current (not `as_of`) reads,
+/// returning when the first one applied to them expires.
+async fn with_current_overrides(
+    state: &AppState,
+    mut countries: Vec<Country>,
+    as_of: Option<DateTime<Utc>>,
+) -> Result<(Vec<Country>, Option<DateTime<Utc>>), sqlx::Error> {
+    if as_of.is_some() {
+        return Ok((countries, None));
+    }
+
+    let overrides = state.overrides.active_overrides(Utc::now()).await?;
+    apply_rate_overrides(&mut countries, &overrides.rates);
+    let expires_at = overrides.next_expiry_for(&countries);
+
+    Ok((countries, expires_at))
+}
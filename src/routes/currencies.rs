@@ -0,0 +1,204 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    auth::Principal,
+    error::AppError,
+    extract::ValidatedJson,
+    models::{
+        audit::{AuditAction, NewAuditEvent},
+        rate_override::{RateOverride, RateOverrideRequest},
+        requests::CountryFilters,
+        responses::ApiError,
+        state::AppState,
+    },
+    utils::{
+        countries::{apply_rate_overrides, country_changes},
+        validation::is_currency_code,
+    },
+};
+
+/// `rate_overrides.rate` is `DECIMAL(20, 8)`: 12 digits before the point
+/// and 8 after.
+const RATE_INTEGER_DIGITS: u64 = 12;
+const RATE_SCALE: i64 = 8;
+
+/// `rate_overrides.expires_at` is a MySQL `TIMESTAMP`, which ends with the
+/// 32-bit Unix epoch.
+const MAX_EXPIRES_AT_SECS: i64 = i32::MAX as i64 + 1;
+
+#[utoipa::path(
+    put,
+    path = "/currencies/{code}/override",
+    params(
+        ("code" = String, Path, description = "ISO 4217 currency code (e.g. \"NGN\")")
+    ),
+    request_body = RateOverrideRequest,
+    responses(
+        (status = 200, description = "Override stored", body = RateOverride),
+        (status = 400, description = "Validation failed", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "Currencies"
+)]
+pub async fn put_rate_override(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    principal: Option<Extension<Principal>>,
+    ValidatedJson(request): ValidatedJson<RateOverrideRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let code = code.to_uppercase();
+    let mut errors = Map::new();
+
+    if !is_currency_code(&code) {
+        errors.insert(
+            "code".to_string(),
+            json!("must be a 3-letter currency code"),
+        );
+    }
+
+    match &request.rate {
+        None => {
+            errors.insert("rate".to_string(), json!("is required"));
+        }
+        Some(rate) if *rate <= BigDecimal::zero() => {
+            errors.insert("rate".to_string(), json!("must be greater than 0"));
+        }
+        Some(rate)
+            if rate.with_scale(0).digits() > RATE_INTEGER_DIGITS
+                || rate.with_scale(RATE_SCALE) != *rate =>
+        {
+            errors.insert(
+                "rate".to_string(),
+                json!(format!(
+                    "must have at most {} digits before the decimal point and {} after",
+                    RATE_INTEGER_DIGITS, RATE_SCALE
+                )),
+            );
+        }
+        Some(_) => {}
+    }
+
+    let reason = request.reason.as_deref().map(str::trim).unwrap_or_default();
+    if reason.is_empty() {
+        errors.insert("reason".to_string(), json!("is required"));
+    }
+
+    let expires_at = match request.expires_at.as_deref() {
+        None => {
+            errors.insert("expires_at".to_string(), json!("is required"));
+            None
+        }
+        Some(expires_at) => match DateTime::parse_from_rfc3339(expires_at) {
+            Ok(expires_at) if expires_at.with_timezone(&Utc) <= Utc::now() => {
+                errors.insert("expires_at".to_string(), json!("must be in the future"));
+                None
+            }
+            Ok(expires_at) if expires_at.timestamp() >= MAX_EXPIRES_AT_SECS => {
+                errors.insert(
+                    "expires_at".to_string(),
+                    json!("must be before 2038-01-19T03:14:08Z"),
+                );
+                None
+            }
+            Ok(expires_at) => Some(expires_at.with_timezone(&Utc)),
+            Err(_) => {
+                errors.insert(
+                    "expires_at".to_string(),
+                    json!("must be an RFC 3339 timestamp"),
+                );
+                None
+            }
+        },
+    };
+
+    let (Some(rate), Some(expires_at), true) = (&request.rate, expires_at, errors.is_empty())
+    else {
+        return Err(AppError::Validation(Value::Object(errors)));
+    };
+
+    let events = override_audit_events(&state, &code, Some(rate), principal).await?;
+    let rate_override = state
+        .overrides
+        .upsert(&code, rate, reason, expires_at, &events)
+        .await?;
+
+    Ok((StatusCode::OK, Json(rate_override)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/currencies/{code}/override",
+    params(
+        ("code" = String, Path, description = "ISO 4217 currency code (e.g. \"NGN\")")
+    ),
+    responses(
+        (status = 204),
+        (status = 404, description = "No override for this currency", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "Currencies"
+)]
+pub async fn delete_rate_override(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    principal: Option<Extension<Principal>>,
+) -> Result<impl IntoResponse, AppError> {
+    let code = code.to_uppercase();
+    let events = override_audit_events(&state, &code, None, principal).await?;
+
+    if !state.overrides.delete(&code, &events).await? {
+        return Err(AppError::NotFound("Rate override not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Audit events for the countries whose current rate changes when the
+/// override for `code` is set to `rate`, or removed when `rate` is `None`.
+async fn override_audit_events(
+    state: &AppState,
+    code: &str,
+    rate: Option<&BigDecimal>,
+    principal: Option<Extension<Principal>>,
+) -> Result<Vec<NewAuditEvent>, AppError> {
+    let filters = CountryFilters {
+        currency: Some(code.to_string()),
+        ..Default::default()
+    };
+    let mut before = state.repository.filter(&filters, None).await?;
+    let mut overrides = state.overrides.active_rates(Utc::now()).await?;
+    apply_rate_overrides(&mut before, &overrides);
+
+    match rate {
+        Some(rate) => overrides.insert(code.to_string(), rate.clone()),
+        None => overrides.remove(code),
+    };
+    let mut after = before.clone();
+    apply_rate_overrides(&mut after, &overrides);
+
+    let actor = principal.map(|Extension(principal)| principal.subject);
+
+    Ok(before
+        .iter()
+        .zip(&after)
+        .filter_map(|(old, new)| {
+            let changes = country_changes(Some(old), Some(new));
+
+            (!changes.is_empty()).then(|| NewAuditEvent {
+                country_name: new.name.clone(),
+                action: AuditAction::Update,
+                source: "api".to_string(),
+                actor: actor.clone(),
+                changes,
+            })
+        })
+        .collect())
+}
@@ -1 +1,4 @@
+pub mod audit;
 pub mod countries;
+pub mod currencies;
+pub mod refreshes;
@@ -0,0 +1,74 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::{
+    error::AppError,
+    models::{
+        quarantine::QuarantinedRecord, refresh::RefreshDiff, responses::ApiError, state::AppState,
+    },
+};
+
+#[utoipa::path(
+    get,
+    path = "/refreshes/{id}/diff",
+    params(
+        ("id" = i64, Path, description = "The refresh ID returned by POST /countries/refresh")
+    ),
+    responses(
+        (status = 200, description = "What the refresh changed", body = RefreshDiff),
+        (status = 404, description = "Refresh not found", body = ApiError),
+        (status = 409, description = "Refresh has not completed", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "Refreshes"
+)]
+pub async fn get_refresh_diff(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let refresh = state
+        .refreshes
+        .get(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Refresh not found".to_string()))?;
+
+    match refresh.diff {
+        Some(diff) => Ok((StatusCode::OK, Json(diff))),
+        None => Err(AppError::Conflict {
+            code: "refresh_incomplete",
+            message: "Refresh diff not available".to_string(),
+            details: Some(json!({ "status": refresh.status })),
+        }),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/refreshes/{id}/quarantine",
+    params(
+        ("id" = i64, Path, description = "The refresh ID returned by POST /countries/refresh")
+    ),
+    responses(
+        (status = 200, description = "Upstream records rejected by the refresh", body = Vec<QuarantinedRecord>),
+        (status = 404, description = "Refresh not found", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "Refreshes"
+)]
+pub async fn get_refresh_quarantine(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    if state.refreshes.get(id).await?.is_none() {
+        return Err(AppError::NotFound("Refresh not found".to_string()));
+    }
+
+    let records = state.quarantine.list_for_refresh(id).await?;
+
+    Ok((StatusCode::OK, Json(records)))
+}
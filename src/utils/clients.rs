@@ -1,52 +1,218 @@
-use std::time::Duration;
+use std::{collections::HashMap, str::FromStr};
 
-use reqwest::{Client, Error};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, RoundingMode};
+use chrono::NaiveDate;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 
 use crate::models::responses::CountryResponse;
+use crate::models::responses::CountryResponseV3;
 use crate::models::responses::ExchangeRateResponse;
+use crate::utils::rates::ExchangeRateProvider;
+use crate::utils::resilience::ResilientClient;
+use crate::utils::sources::CountrySource;
+use crate::utils::upstream_cache::{UpstreamCache, Validators};
+use crate::utils::validation::{ParsedCountries, parse_country_records};
+
+/// Fetches `url` with the validators stored for `upstream`, returning `None`
+/// when the upstream answers `304 Not Modified`.
+async fn fetch_if_modified<T: DeserializeOwned>(
+    client: &ResilientClient,
+    cache: &UpstreamCache,
+    upstream: &str,
+    url: &str,
+) -> Result<Option<T>> {
+    let headers = cache.validators(upstream).request_headers();
+    let response = client.get_with_headers(url, headers).await?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        tracing::info!("{} reported no changes since the last fetch", upstream);
+        return Ok(None);
+    }
+
+    let validators = Validators::from_response(&response);
+    let body = response.json::<T>().await?;
+    cache.store_validators(upstream, validators);
+
+    Ok(Some(body))
+}
 
 pub struct CountriesApiClient {
-    client: Client,
+    client: ResilientClient,
+    cache: UpstreamCache,
     api_url: String,
 }
 
 impl CountriesApiClient {
-    pub fn new(api_url: String) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
+    pub fn new(api_url: String, client: ResilientClient, cache: UpstreamCache) -> Self {
+        Self {
+            client,
+            cache,
+            api_url,
+        }
+    }
 
-        Self { client, api_url }
+    pub async fn fetch_all_countries(&self) -> Result<Option<ParsedCountries>> {
+        let records =
+            fetch_if_modified::<Vec<Value>>(&self.client, &self.cache, self.name(), &self.api_url)
+                .await?;
+
+        Ok(records.map(|records| parse_country_records::<CountryResponse>(self.name(), records)))
     }
+}
 
-    pub async fn fetch_all_countries(&self) -> Result<Vec<CountryResponse>, Error> {
-        let response = self.client.get(&self.api_url).send().await?;
-        let countries = response.json::<Vec<CountryResponse>>().await?;
+#[async_trait]
+impl CountrySource for CountriesApiClient {
+    fn name(&self) -> &str {
+        "restcountries_v2"
+    }
 
-        Ok(countries)
+    async fn fetch_countries(&self) -> Result<Option<ParsedCountries>> {
+        self.fetch_all_countries().await
+    }
+}
+
+pub struct CountriesApiV3Client {
+    client: ResilientClient,
+    cache: UpstreamCache,
+    api_url: String,
+}
+
+impl CountriesApiV3Client {
+    pub fn new(api_url: String, client: ResilientClient, cache: UpstreamCache) -> Self {
+        Self {
+            client,
+            cache,
+            api_url,
+        }
+    }
+}
+
+#[async_trait]
+impl CountrySource for CountriesApiV3Client {
+    fn name(&self) -> &str {
+        "restcountries_v3"
+    }
+
+    async fn fetch_countries(&self) -> Result<Option<ParsedCountries>> {
+        let records =
+            fetch_if_modified::<Vec<Value>>(&self.client, &self.cache, self.name(), &self.api_url)
+                .await?;
+
+        Ok(records.map(|records| parse_country_records::<CountryResponseV3>(self.name(), records)))
     }
 }
 
 pub struct ExchangeApiClient {
-    client: Client,
+    client: ResilientClient,
+    cache: UpstreamCache,
     api_url: String,
 }
 
 impl ExchangeApiClient {
-    pub fn new(api_url: String) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
+    pub fn new(api_url: String, client: ResilientClient, cache: UpstreamCache) -> Self {
+        Self {
+            client,
+            cache,
+            api_url,
+        }
+    }
+}
 
-        Self { client, api_url }
+#[async_trait]
+impl ExchangeRateProvider for ExchangeApiClient {
+    fn name(&self) -> &str {
+        "open_er_api"
     }
 
-    pub async fn fetch_rates(&self) -> Result<ExchangeRateResponse, Error> {
-        let response = self.client.get(&self.api_url).send().await?;
+    async fn fetch_rates(&self) -> Result<ExchangeRateResponse> {
+        // The feed publishes when its next update is due; until then the last
+        // response is still current
+        if let Some(rates) = self.cache.fresh_rates(self.name()) {
+            tracing::info!("Reusing {} rates until their next update", self.name());
+            return Ok(rates);
+        }
+
+        let response = self.client.get(&self.api_url).await?;
         let rates = response.json::<ExchangeRateResponse>().await?;
+        self.cache.store_rates(self.name(), &rates);
 
         Ok(rates)
     }
 }
+
+/// Reads an ECB-style daily XML feed (`<Cube currency=".." rate=".."/>`, EUR based)
+/// and rebases it to USD so it lines up with the other providers.
+pub struct EcbRatesClient {
+    client: ResilientClient,
+    api_url: String,
+}
+
+impl EcbRatesClient {
+    pub fn new(api_url: String, client: ResilientClient) -> Self {
+        Self { client, api_url }
+    }
+}
+
+#[async_trait]
+impl ExchangeRateProvider for EcbRatesClient {
+    fn name(&self) -> &str {
+        "ecb"
+    }
+
+    async fn fetch_rates(&self) -> Result<ExchangeRateResponse> {
+        let response = self.client.get(&self.api_url).await?;
+        let body = response.text().await?;
+
+        parse_ecb_rates(&body)
+    }
+}
+
+pub fn parse_ecb_rates(xml: &str) -> Result<ExchangeRateResponse> {
+    let document = roxmltree::Document::parse(xml)?;
+
+    let mut eur_rates = HashMap::new();
+    let mut published = None;
+    for node in document
+        .descendants()
+        .filter(|node| node.has_tag_name("Cube"))
+    {
+        if let Some(time) = node.attribute("time") {
+            published = NaiveDate::parse_from_str(time, "%Y-%m-%d").ok();
+        }
+
+        if let (Some(currency), Some(rate)) = (node.attribute("currency"), node.attribute("rate")) {
+            eur_rates.insert(currency.to_string(), BigDecimal::from_str(rate)?);
+        }
+    }
+
+    let usd_per_eur = eur_rates
+        .get("USD")
+        .cloned()
+        .ok_or_else(|| anyhow!("ECB feed has no USD rate"))?;
+
+    let mut rates = eur_rates
+        .into_iter()
+        .map(|(currency, rate)| {
+            let rebased = (rate / &usd_per_eur).with_scale_round(8, RoundingMode::HalfEven);
+            (currency, rebased)
+        })
+        .collect::<HashMap<String, BigDecimal>>();
+
+    rates.insert(
+        "EUR".to_string(),
+        (BigDecimal::from(1) / &usd_per_eur).with_scale_round(8, RoundingMode::HalfEven),
+    );
+    rates.insert("USD".to_string(), BigDecimal::from(1));
+
+    Ok(ExchangeRateResponse {
+        rates,
+        time_last_update_unix: published
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|time| time.and_utc().timestamp()),
+        ..Default::default()
+    })
+}
@@ -1,8 +1,14 @@
 use anyhow::{Ok, Result, anyhow};
+use axum::http::HeaderValue;
 use dotenvy::dotenv;
 use envy::from_env;
 use serde::Deserialize;
 
+use crate::{
+    error::ErrorFormat,
+    utils::{decimal::DecimalFormat, rates::RateProviderKind, sources::CountrySourceKind},
+};
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub database_url: String,
@@ -11,7 +17,219 @@ pub struct Config {
     pub server_host: String,
     pub server_port: u32,
     pub rest_countries_api: String,
+    /// Which layout `rest_countries_api` serves, or `file` to read `countries_file`
+    #[serde(default)]
+    pub country_source: CountrySourceKind,
+    /// Local JSON file in the restcountries v2 layout, required by the `file` source
+    pub countries_file: Option<String>,
     pub exchange_rates_api: String,
+    /// Ordered fallback chain of exchange rate providers
+    #[serde(default = "default_exchange_rate_providers")]
+    pub exchange_rate_providers: Vec<RateProviderKind>,
+    #[serde(default = "default_ecb_rates_api")]
+    pub ecb_rates_api: String,
+    /// Local JSON or CSV rates file, required by the `file` provider
+    pub exchange_rates_file: Option<String>,
+    /// Proxy URL for every upstream request (e.g. "http://proxy.internal:3128")
+    pub upstream_proxy: Option<String>,
+    /// PEM file of extra root certificates to trust for upstream requests
+    pub upstream_ca_bundle: Option<String>,
+    /// Extra `Name: value` headers sent to every upstream, one per line.
+    /// Not comma-separated, since header values may contain commas.
+    #[serde(default)]
+    pub upstream_headers: String,
+    #[serde(default = "default_upstream_user_agent")]
+    pub upstream_user_agent: String,
+    /// Sent only to the `open_er_api` provider, in `exchange_rates_api_key_header`
+    pub exchange_rates_api_key: Option<String>,
+    #[serde(default = "default_exchange_rates_api_key_header")]
+    pub exchange_rates_api_key_header: String,
+    /// Retries after the first failed upstream request
+    #[serde(default = "default_upstream_max_retries")]
+    pub upstream_max_retries: u32,
+    #[serde(default = "default_upstream_retry_base_delay_ms")]
+    pub upstream_retry_base_delay_ms: u64,
+    #[serde(default = "default_upstream_retry_max_delay_ms")]
+    pub upstream_retry_max_delay_ms: u64,
+    /// Consecutive upstream failures before its circuit breaker opens
+    #[serde(default = "default_circuit_breaker_failure_threshold")]
+    pub circuit_breaker_failure_threshold: u32,
+    /// How long an open circuit breaker rejects requests before a trial request
+    #[serde(default = "default_circuit_breaker_open_secs")]
+    pub circuit_breaker_open_secs: u64,
+    /// Exchange rates published longer ago than this are flagged as stale
+    #[serde(default = "default_rate_stale_after_hours")]
+    pub rate_stale_after_hours: u64,
+    /// Minimum exchange rate move, in percent, reported in a refresh diff
+    #[serde(default = "default_rate_change_threshold")]
+    pub rate_change_threshold: f64,
+    /// How often country data is expected to be refreshed. Reads may be
+    /// cached by clients until the next refresh is due.
+    #[serde(default = "default_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+    /// How long current country reads are served from memory; 0 disables the
+    /// cache. Writes through this instance clear it immediately.
+    #[serde(default = "default_country_cache_ttl_secs")]
+    pub country_cache_ttl_secs: u64,
+    /// Apply embedded migrations on startup
+    #[serde(default)]
+    pub run_migrations: bool,
+    /// Whether decimals are written as JSON numbers or strings
+    #[serde(default)]
+    pub decimal_format: DecimalFormat,
+    /// Require an API key on every endpoint except the docs
+    #[serde(default)]
+    pub auth_enabled: bool,
+    /// Per-client token bucket limits on read and refresh routes
+    #[serde(default)]
+    pub rate_limit_enabled: bool,
+    #[serde(default = "default_rate_limit_read_per_minute")]
+    pub rate_limit_read_per_minute: u32,
+    /// Requests a client can make at once before being held to the per-minute rate
+    #[serde(default = "default_rate_limit_read_burst")]
+    pub rate_limit_read_burst: u32,
+    #[serde(default = "default_rate_limit_refresh_per_minute")]
+    pub rate_limit_refresh_per_minute: u32,
+    #[serde(default = "default_rate_limit_refresh_burst")]
+    pub rate_limit_refresh_burst: u32,
+    /// Failed authentications allowed per address, checked before credentials
+    /// are looked up
+    #[serde(default = "default_rate_limit_auth_failures_per_minute")]
+    pub rate_limit_auth_failures_per_minute: u32,
+    #[serde(default = "default_rate_limit_auth_failures_burst")]
+    pub rate_limit_auth_failures_burst: u32,
+    /// Key anonymous clients by the last `X-Forwarded-For` address, the one
+    /// the proxy appended, rather than the peer address. Only safe behind
+    /// exactly one proxy that appends to it.
+    #[serde(default)]
+    pub rate_limit_trust_forwarded_for: bool,
+    /// JSON Web Key Set for verifying bearer tokens, read once at startup
+    pub jwks_file: Option<String>,
+    /// Fetched at startup, and again when a token names an unknown `kid`
+    pub jwks_url: Option<String>,
+    /// Required `iss` claim, needed with a JWKS
+    pub jwt_issuer: Option<String>,
+    /// Required `aud` claim, needed with a JWKS
+    pub jwt_audience: Option<String>,
+    /// Clock skew allowed when checking `exp` and `nbf`
+    #[serde(default = "default_jwt_leeway_secs")]
+    pub jwt_leeway_secs: u64,
+    /// Browser origins allowed by CORS, comma-separated, or `*` for any.
+    /// CORS headers are only sent when this is set.
+    #[serde(default)]
+    pub cors_allowed_origins: Vec<String>,
+    /// brotli, gzip or deflate responses for clients that accept it
+    #[serde(default = "default_compression_enabled")]
+    pub compression_enabled: bool,
+    /// Smaller responses are sent uncompressed
+    #[serde(default = "default_compression_min_bytes")]
+    pub compression_min_bytes: usize,
+    /// Requests taking longer are answered with `503`; 0 disables the limit.
+    /// Refreshes are exempt, since upstream retries can outlast it.
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// Larger request bodies are rejected with `413`
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Whether errors are `ApiError` JSON or RFC 7807 problem details
+    #[serde(default)]
+    pub error_format: ErrorFormat,
+}
+
+fn default_exchange_rate_providers() -> Vec<RateProviderKind> {
+    vec![RateProviderKind::OpenErApi]
+}
+
+fn default_ecb_rates_api() -> String {
+    "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml".to_string()
+}
+
+fn default_upstream_user_agent() -> String {
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string()
+}
+
+fn default_exchange_rates_api_key_header() -> String {
+    "Authorization".to_string()
+}
+
+fn default_upstream_max_retries() -> u32 {
+    3
+}
+
+fn default_upstream_retry_base_delay_ms() -> u64 {
+    200
+}
+
+fn default_upstream_retry_max_delay_ms() -> u64 {
+    5000
+}
+
+fn default_circuit_breaker_failure_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_breaker_open_secs() -> u64 {
+    60
+}
+
+fn default_rate_stale_after_hours() -> u64 {
+    48
+}
+
+fn default_refresh_interval_secs() -> u64 {
+    3600
+}
+
+fn default_country_cache_ttl_secs() -> u64 {
+    300
+}
+
+fn default_rate_limit_read_per_minute() -> u32 {
+    120
+}
+
+fn default_rate_limit_read_burst() -> u32 {
+    60
+}
+
+fn default_rate_limit_refresh_per_minute() -> u32 {
+    1
+}
+
+fn default_rate_limit_refresh_burst() -> u32 {
+    2
+}
+
+fn default_rate_limit_auth_failures_per_minute() -> u32 {
+    10
+}
+
+fn default_rate_limit_auth_failures_burst() -> u32 {
+    10
+}
+
+fn default_compression_enabled() -> bool {
+    true
+}
+
+fn default_compression_min_bytes() -> usize {
+    1024
+}
+
+fn default_request_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_body_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_jwt_leeway_secs() -> u64 {
+    60
+}
+
+fn default_rate_change_threshold() -> f64 {
+    1.0
 }
 
 pub fn load_config() -> Result<Config> {
@@ -19,5 +237,62 @@ pub fn load_config() -> Result<Config> {
 
     let config = from_env::<Config>().map_err(|e| anyhow!("Configuration error: {}", e))?;
 
+    if config
+        .exchange_rate_providers
+        .contains(&RateProviderKind::File)
+        && config.exchange_rates_file.is_none()
+    {
+        return Err(anyhow!(
+            "Configuration error: EXCHANGE_RATES_FILE is required by the file rate provider"
+        ));
+    }
+
+    if config.country_source == CountrySourceKind::File && config.countries_file.is_none() {
+        return Err(anyhow!(
+            "Configuration error: COUNTRIES_FILE is required by the file country source"
+        ));
+    }
+
+    if config.rate_limit_enabled
+        && [
+            config.rate_limit_read_per_minute,
+            config.rate_limit_read_burst,
+            config.rate_limit_refresh_per_minute,
+            config.rate_limit_refresh_burst,
+            config.rate_limit_auth_failures_per_minute,
+            config.rate_limit_auth_failures_burst,
+        ]
+        .contains(&0)
+    {
+        return Err(anyhow!(
+            "Configuration error: rate limits and bursts must be at least 1"
+        ));
+    }
+
+    if let Some(origin) = config
+        .cors_allowed_origins
+        .iter()
+        .find(|origin| origin.as_str() != "*" && HeaderValue::from_str(origin).is_err())
+    {
+        return Err(anyhow!(
+            "Configuration error: invalid CORS origin {:?}",
+            origin
+        ));
+    }
+
+    if config.jwks_file.is_some() && config.jwks_url.is_some() {
+        return Err(anyhow!(
+            "Configuration error: set only one of JWKS_FILE and JWKS_URL"
+        ));
+    }
+
+    if (config.jwks_file.is_some() || config.jwks_url.is_some())
+        && (config.jwt_issuer.is_none() || config.jwt_audience.is_none())
+    {
+        return Err(anyhow!(
+            "Configuration error: JWT_ISSUER and JWT_AUDIENCE are required with a JWKS"
+        ));
+    }
+
     Ok(config)
 }
@@ -1,16 +1,39 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
+use chrono::{DateTime, Duration, Utc};
 use rand::random_range;
+use serde_json::{Map, Value, json};
 
-use crate::models::responses::Currency;
+use crate::models::{
+    country::Country,
+    refresh::{
+        CurrencyChange, PopulationChange, PreviewGroup, RateChange, RefreshDiff, RefreshPreview,
+    },
+    responses::{CountryResponse, Currency},
+};
 
+/// Fields compared when recording what changed about a country. `estimated_gdp`
+/// is left out on purpose since it is re-randomised on every refresh.
+const AUDITED_FIELDS: [&str; 6] = [
+    "capital",
+    "region",
+    "population",
+    "currency_code",
+    "exchange_rate",
+    "flag_url",
+];
+
+/// Picks the country's first currency and its rate, preferring an active
+/// manual override to the feed rate, and estimates GDP from it.
 pub fn process_currency_and_gdp(
     currencies: Option<&Vec<Currency>>,
     population: i64,
-    rates: &HashMap<String, f64>,
-) -> (Option<String>, Option<f64>, Option<f64>) {
+    rates: &HashMap<String, BigDecimal>,
+    overrides: &HashMap<String, BigDecimal>,
+) -> (Option<String>, Option<BigDecimal>, Option<BigDecimal>) {
     if currencies.is_none() || currencies.unwrap().is_empty() {
-        return (None, None, Some(0.0));
+        return (None, None, Some(BigDecimal::zero()));
     }
 
     let currencies = currencies.unwrap();
@@ -18,25 +41,274 @@ pub fn process_currency_and_gdp(
 
     let currency_code = match &first_currency.code {
         Some(code) => code.clone(),
-        None => return (None, None, Some(0.0)),
+        None => return (None, None, Some(BigDecimal::zero())),
     };
 
-    match rates.get(&currency_code) {
+    match overrides
+        .get(&currency_code)
+        .or_else(|| rates.get(&currency_code))
+    {
         Some(rate) => {
-            let estimated_gdp = calculate_gdp(population, *rate);
+            let estimated_gdp = calculate_gdp(population, rate);
 
-            (Some(currency_code), Some(*rate), estimated_gdp)
+            (Some(currency_code), Some(rate.clone()), estimated_gdp)
         }
         None => (Some(currency_code), None, None),
     }
 }
 
-pub fn calculate_gdp(population: i64, exchange_rate: f64) -> Option<f64> {
-    if exchange_rate == 0.0 {
+pub const RATE_SOURCE_FEED: &str = "feed";
+pub const RATE_SOURCE_OVERRIDE: &str = "override";
+
+/// Brings stored countries in line with the overrides active now: an override
+/// created since the last refresh replaces the feed rate, and an expired or
+/// removed one gives way to the feed rate again. GDP is rescaled by the rate
+/// ratio so its random multiplier is kept.
+pub fn apply_rate_overrides(countries: &mut [Country], overrides: &HashMap<String, BigDecimal>) {
+    for country in countries {
+        let Some(code) = &country.currency_code else {
+            continue;
+        };
+
+        let (rate, source) = match overrides.get(code) {
+            Some(rate) => (rate.clone(), RATE_SOURCE_OVERRIDE),
+            None => match &country.feed_exchange_rate {
+                Some(rate) => (rate.clone(), RATE_SOURCE_FEED),
+                None => continue,
+            },
+        };
+
+        if country.exchange_rate.as_ref() == Some(&rate) {
+            continue;
+        }
+
+        if let (Some(gdp), Some(old_rate)) = (&country.estimated_gdp, &country.exchange_rate)
+            && !rate.is_zero()
+        {
+            country.estimated_gdp =
+                Some((gdp * old_rate / &rate).with_scale_round(2, RoundingMode::HalfEven));
+        }
+        country.exchange_rate = Some(rate);
+        country.rate_source = Some(source.to_string());
+    }
+}
+
+/// Sets `is_stale` on countries whose rate was published more than `max_age`
+/// before `reference`, returning whether any were.
+pub fn flag_stale_rates(
+    countries: &mut [Country],
+    max_age: Duration,
+    reference: DateTime<Utc>,
+) -> bool {
+    let mut any_stale = false;
+
+    for country in countries {
+        // A pinned rate is deliberate, however old the feed behind it is
+        country.is_stale = country.rate_source.as_deref() != Some(RATE_SOURCE_OVERRIDE)
+            && country
+                .rate_as_of
+                .as_ref()
+                .and_then(|as_of| as_of.parse::<DateTime<Utc>>().ok())
+                .is_some_and(|as_of| reference - as_of > max_age);
+        any_stale |= country.is_stale;
+    }
+
+    any_stale
+}
+
+/// Rebuilds the upstream record a stored country came from, for refreshes where
+/// the country source reported no changes and only rates need reapplying.
+pub fn stored_country_response(country: &Country) -> CountryResponse {
+    CountryResponse {
+        name: country.name.clone(),
+        alpha2_code: country.alpha2_code.clone(),
+        alpha3_code: country.alpha3_code.clone(),
+        capital: country.capital.clone(),
+        region: country.region.clone(),
+        population: country.population,
+        currencies: country.currency_code.as_ref().map(|code| {
+            vec![Currency {
+                code: Some(code.clone()),
+                name: None,
+                symbol: None,
+            }]
+        }),
+        flag: country.flag_url.clone(),
+        independent: false,
+    }
+}
+
+/// Currency codes `process_currency_and_gdp` will look up, i.e. the first
+/// currency of each country.
+pub fn primary_currency_codes(countries: &[CountryResponse]) -> HashSet<String> {
+    countries
+        .iter()
+        .filter_map(|country| country.currencies.as_ref()?.first()?.code.clone())
+        .collect()
+}
+
+pub fn calculate_gdp(population: i64, exchange_rate: &BigDecimal) -> Option<BigDecimal> {
+    if exchange_rate.is_zero() {
         return None;
     }
 
-    let multiplier = random_range(1000.0..=2000.0);
+    // 1000.00..=2000.00, kept in hundredths so the multiplier is an exact decimal
+    let multiplier = BigDecimal::new(random_range(100_000..=200_000).into(), 2);
+
+    let gdp = BigDecimal::from(population) * multiplier / exchange_rate;
+
+    Some(gdp.with_scale_round(2, RoundingMode::HalfEven))
+}
+
+pub fn country_changes(old: Option<&Country>, new: Option<&Country>) -> Map<String, Value> {
+    let old = old.map(|c| json!(c)).unwrap_or(Value::Null);
+    let new = new.map(|c| json!(c)).unwrap_or(Value::Null);
+
+    AUDITED_FIELDS
+        .iter()
+        .filter_map(|field| {
+            let old_value = old.get(field).cloned().unwrap_or(Value::Null);
+            let new_value = new.get(field).cloned().unwrap_or(Value::Null);
+
+            if same_value(field, &old_value, &new_value) {
+                return None;
+            }
+
+            Some((
+                field.to_string(),
+                json!({ "old": old_value, "new": new_value }),
+            ))
+        })
+        .collect()
+}
+
+/// Decimal fields, compared by value: a stored `15.34000000` and a freshly
+/// fetched `15.34` are the same rate.
+const DECIMAL_FIELDS: [&str; 3] = ["exchange_rate", "feed_exchange_rate", "estimated_gdp"];
+
+fn same_value(field: &str, old: &Value, new: &Value) -> bool {
+    let decimal = |value: &Value| match value {
+        Value::Number(number) => number.to_string().parse::<BigDecimal>().ok(),
+        // `DECIMAL_FORMAT=string`
+        Value::String(text) => text.parse::<BigDecimal>().ok(),
+        _ => None,
+    };
+
+    old == new
+        || (DECIMAL_FIELDS.contains(&field)
+            && matches!((decimal(old), decimal(new)), (Some(old), Some(new)) if old == new))
+}
+
+/// Compares freshly processed countries against the stored rows, keyed by
+/// lowercased name. Rate moves are only reported when they exceed
+/// `rate_threshold_percent` of the old rate.
+pub fn compute_refresh_diff(
+    existing: &HashMap<String, Country>,
+    incoming: &[Country],
+    rate_threshold_percent: f64,
+) -> RefreshDiff {
+    let mut diff = RefreshDiff::default();
+    let mut seen = HashSet::new();
+
+    for country in incoming {
+        let key = country.name.to_lowercase();
+        seen.insert(key.clone());
+
+        let Some(previous) = existing.get(&key) else {
+            diff.added.push(country.name.clone());
+            continue;
+        };
+
+        if previous.population != country.population {
+            diff.population_changes.push(PopulationChange {
+                name: country.name.clone(),
+                old: previous.population,
+                new: country.population,
+            });
+        }
+
+        if previous.currency_code != country.currency_code {
+            diff.currency_changes.push(CurrencyChange {
+                name: country.name.clone(),
+                old: previous.currency_code.clone(),
+                new: country.currency_code.clone(),
+            });
+        }
+
+        let rate_change = |change_percent| RateChange {
+            name: country.name.clone(),
+            currency_code: country.currency_code.clone(),
+            old: previous.exchange_rate.clone(),
+            new: country.exchange_rate.clone(),
+            change_percent,
+        };
+
+        match (&previous.exchange_rate, &country.exchange_rate) {
+            (Some(old), Some(new)) if !old.is_zero() => {
+                let change_percent = ((new - old) / old * BigDecimal::from(100))
+                    .to_f64()
+                    .unwrap_or_default();
+
+                if change_percent.abs() > rate_threshold_percent {
+                    diff.rate_changes.push(rate_change(Some(change_percent)));
+                }
+            }
+            // A rate that appeared, disappeared or moved off zero has no percentage
+            (old, new) if old != new => diff.rate_changes.push(rate_change(None)),
+            _ => {}
+        }
+    }
+
+    diff.missing_upstream = existing
+        .iter()
+        .filter(|(key, _)| !seen.contains(*key))
+        .map(|(_, country)| country.name.clone())
+        .collect();
+    diff.missing_upstream.sort();
+
+    diff
+}
+
+/// Splits freshly processed countries into what a refresh would insert, update
+/// or leave stale, keeping up to `sample_size` example rows of each.
+pub fn preview_refresh(
+    existing: &HashMap<String, Country>,
+    incoming: &[Country],
+    sample_size: usize,
+) -> RefreshPreview {
+    let mut preview = RefreshPreview::default();
+    let mut seen = HashSet::new();
+
+    let push = |group: &mut PreviewGroup, country: &Country| {
+        group.count += 1;
+        if group.sample.len() < sample_size {
+            group.sample.push(country.clone());
+        }
+    };
+
+    for country in incoming {
+        let key = country.name.to_lowercase();
+        seen.insert(key.clone());
+
+        match existing.get(&key) {
+            None => push(&mut preview.inserted, country),
+            Some(previous) if !country_changes(Some(previous), Some(country)).is_empty() => {
+                push(&mut preview.updated, country)
+            }
+            Some(_) => preview.unchanged += 1,
+        }
+    }
+
+    let mut missing_upstream = existing
+        .iter()
+        .filter(|(key, _)| !seen.contains(*key))
+        .map(|(_, country)| country)
+        .collect::<Vec<&Country>>();
+    missing_upstream.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for country in missing_upstream {
+        push(&mut preview.missing_upstream, country);
+    }
 
-    Some((population as f64 * multiplier) / exchange_rate)
+    preview
 }
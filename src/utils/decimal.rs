@@ -0,0 +1,47 @@
+use std::sync::OnceLock;
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize, Serializer, ser::Error};
+
+/// How `BigDecimal` values are written in JSON responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecimalFormat {
+    /// Arbitrary-precision JSON numbers, e.g. `1600.23000000`
+    #[default]
+    Number,
+    /// JSON strings, e.g. `"1600.23000000"`, for clients that parse numbers as doubles
+    String,
+}
+
+static DECIMAL_FORMAT: OnceLock<DecimalFormat> = OnceLock::new();
+
+/// Sets the process-wide decimal format. Only the first call takes effect.
+pub fn set_decimal_format(format: DecimalFormat) {
+    let _ = DECIMAL_FORMAT.set(format);
+}
+
+pub fn serialize<S>(value: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match DECIMAL_FORMAT.get().copied().unwrap_or_default() {
+        // `to_string` switches to exponent notation for small values
+        DecimalFormat::Number => value
+            .to_plain_string()
+            .parse::<serde_json::Number>()
+            .map_err(S::Error::custom)?
+            .serialize(serializer),
+        DecimalFormat::String => serializer.serialize_str(&value.to_plain_string()),
+    }
+}
+
+pub fn serialize_option<S>(value: &Option<BigDecimal>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(value) => serialize(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::{
+    Certificate, Client, Proxy,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+
+use crate::utils::config::Config;
+
+/// `reqwest` clients for upstream requests, built once at startup so a bad proxy
+/// URL, CA bundle or header fails fast. Cloning is cheap: clients share their
+/// connection pool.
+#[derive(Clone)]
+pub struct HttpClients {
+    /// Used for the country sources, the ECB feed and flag images
+    pub default: Client,
+    /// Like `default`, plus the exchange rates API key header when configured
+    pub exchange_rates: Client,
+    /// Like `default`, without `UPSTREAM_HEADERS`, so provider credentials
+    /// aren't sent to the identity provider when fetching the JWKS
+    pub jwks: Client,
+}
+
+impl HttpClients {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let headers = parse_headers(&config.upstream_headers)?;
+
+        let mut exchange_rates_headers = headers.clone();
+        if let Some(key) = &config.exchange_rates_api_key {
+            let name = HeaderName::from_bytes(config.exchange_rates_api_key_header.as_bytes())
+                .context("Invalid EXCHANGE_RATES_API_KEY_HEADER")?;
+            let mut value = HeaderValue::from_str(key).context("Invalid EXCHANGE_RATES_API_KEY")?;
+            value.set_sensitive(true);
+            exchange_rates_headers.insert(name, value);
+        }
+
+        Ok(Self {
+            default: build_client(config, headers)?,
+            exchange_rates: build_client(config, exchange_rates_headers)?,
+            jwks: build_client(config, HeaderMap::new())?,
+        })
+    }
+}
+
+fn build_client(config: &Config, headers: HeaderMap) -> Result<Client> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(30))
+        .user_agent(&config.upstream_user_agent)
+        .default_headers(headers);
+
+    if let Some(proxy) = &config.upstream_proxy {
+        builder = builder.proxy(Proxy::all(proxy).context("Invalid UPSTREAM_PROXY")?);
+    }
+
+    if let Some(path) = &config.upstream_ca_bundle {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read UPSTREAM_CA_BUNDLE {}", path))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid UPSTREAM_CA_BUNDLE {}", path))?;
+
+        if certificates.is_empty() {
+            return Err(anyhow!("UPSTREAM_CA_BUNDLE {} has no certificates", path));
+        }
+
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder.build().context("Failed to create HTTP client")
+}
+
+/// Parses `Name: value` lines, as given in `UPSTREAM_HEADERS`.
+pub fn parse_headers(lines: &str) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    for entry in lines.lines().filter(|entry| !entry.trim().is_empty()) {
+        let (name, value) = entry.split_once(':').ok_or_else(|| {
+            anyhow!(
+                "Invalid UPSTREAM_HEADERS entry {:?}: expected Name: value",
+                entry
+            )
+        })?;
+
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .with_context(|| format!("Invalid header name in UPSTREAM_HEADERS: {:?}", name))?;
+        let value = HeaderValue::from_str(value.trim())
+            .with_context(|| format!("Invalid value for UPSTREAM_HEADERS header {}", name))?;
+
+        headers.append(name, value);
+    }
+
+    Ok(headers)
+}
@@ -0,0 +1,117 @@
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, HeaderValue, Response, StatusCode,
+        header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    },
+};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::utils::config::Config;
+
+/// Validators sent with a `200` and compared against the request's
+/// conditional headers, so an unchanged resource is answered with `304`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseValidators {
+    /// Quoted strong entity tag
+    pub etag: String,
+    /// Omitted when the content can change without this moving (e.g. country
+    /// reads, which deletes change), so clients fall back to the ETag
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl ResponseValidators {
+    /// Tags a response by its exact bytes.
+    pub fn from_body(body: &[u8], last_modified: Option<DateTime<Utc>>) -> Self {
+        Self {
+            etag: format!("\"{}\"", &hex::encode(Sha256::digest(body))[..32]),
+            last_modified,
+        }
+    }
+
+    /// Whether the client's copy is current. `If-None-Match` wins when sent,
+    /// as RFC 9110 requires; `If-Modified-Since` is only checked without it.
+    pub fn matches(&self, request: &HeaderMap) -> bool {
+        if let Some(if_none_match) = request.get(IF_NONE_MATCH) {
+            return if_none_match.to_str().is_ok_and(|tags| {
+                tags.split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag)
+            });
+        }
+
+        let if_modified_since = request
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+
+        match (self.last_modified, if_modified_since) {
+            (Some(last_modified), Some(since)) => last_modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    fn insert(&self, headers: &mut HeaderMap) {
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(ETAG, etag);
+        }
+
+        if let Some(last_modified) = self.last_modified
+            && let Ok(value) = HeaderValue::from_str(
+                &last_modified
+                    .format("%a, %d %b %Y %H:%M:%S GMT")
+                    .to_string(),
+            )
+        {
+            headers.insert(LAST_MODIFIED, value);
+        }
+    }
+}
+
+/// Lets clients reuse a response until the next refresh is due, i.e.
+/// `REFRESH_INTERVAL_SECS` after `last_refreshed`, or until `changes_at` if
+/// that is sooner, e.g. when a rate override in it expires. Responses behind
+/// authentication are only cached by the client itself.
+pub fn cache_control(
+    config: &Config,
+    last_refreshed: Option<DateTime<Utc>>,
+    changes_at: Option<DateTime<Utc>>,
+) -> HeaderValue {
+    let interval = config.refresh_interval_secs as i64;
+    let now = Utc::now();
+    let max_age = last_refreshed
+        .map(|at| {
+            let until = changes_at.map_or(interval, |changes_at| (changes_at - now).num_seconds());
+            (at + Duration::seconds(interval) - now)
+                .num_seconds()
+                .min(until)
+                .clamp(0, interval)
+        })
+        .unwrap_or(0);
+    let visibility = if config.auth_enabled {
+        "private"
+    } else {
+        "public"
+    };
+
+    HeaderValue::from_str(&format!("{}, max-age={}", visibility, max_age))
+        .unwrap_or(HeaderValue::from_static("no-cache"))
+}
+
+/// `304 Not Modified` repeating the validators and freshness of the `200`.
+pub fn not_modified(validators: &ResponseValidators, cache_control: HeaderValue) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::NOT_MODIFIED;
+    with_cache_headers(response, validators, cache_control)
+}
+
+pub fn with_cache_headers(
+    mut response: Response<Body>,
+    validators: &ResponseValidators,
+    cache_control: HeaderValue,
+) -> Response<Body> {
+    validators.insert(response.headers_mut());
+    response.headers_mut().insert(CACHE_CONTROL, cache_control);
+    response
+}
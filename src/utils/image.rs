@@ -6,10 +6,12 @@ use bigdecimal::ToPrimitive;
 use chrono::{DateTime, Utc};
 use image::{DynamicImage, ImageBuffer, Rgb, RgbImage, imageops};
 use imageproc::drawing::{draw_text_mut, text_size};
+use reqwest::Client;
 
 use crate::models::country::Country;
 
 pub async fn generate_summary_image(
+    client: &Client,
     total_countries: i64,
     top_countries: Vec<Country>,
     last_refreshed: DateTime<Utc>,
@@ -63,10 +65,10 @@ pub async fn generate_summary_image(
     for (i, country) in top_countries.iter().take(5).enumerate() {
         let y_pos = 200 + (i as i32 * 60);
 
-        if let Some(flag_url) = &country.flag_url {
-            if let Ok(flag_img) = fetch_and_resize_flag(flag_url, 40, 30).await {
-                overlay_image(&mut img, &flag_img, 50, y_pos as u32);
-            }
+        if let Some(flag_url) = &country.flag_url
+            && let Ok(flag_img) = fetch_and_resize_flag(client, flag_url, 40, 30).await
+        {
+            overlay_image(&mut img, &flag_img, 50, y_pos as u32);
         }
 
         let gdp_text = match &country.estimated_gdp {
@@ -111,13 +113,14 @@ pub async fn generate_summary_image(
 }
 
 async fn fetch_and_resize_flag(
+    client: &Client,
     flag_url: &str,
     target_width: u32,
     target_height: u32,
 ) -> Result<DynamicImage> {
     let png_url = convert_to_png_url(flag_url);
 
-    let response = reqwest::get(&png_url).await?;
+    let response = client.get(&png_url).send().await?;
 
     if !response.status().is_success() {
         return Err(anyhow!("Failed to fetch flag: HTTP {}", response.status()));
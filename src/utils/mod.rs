@@ -1,5 +1,14 @@
 pub mod clients;
 pub mod config;
 pub mod countries;
+pub mod decimal;
+pub mod http;
+pub mod http_cache;
 pub mod image;
+pub mod rate_limit;
+pub mod rates;
+pub mod resilience;
+pub mod sources;
 pub mod tasks;
+pub mod upstream_cache;
+pub mod validation;
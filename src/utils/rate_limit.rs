@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::utils::config::Config;
+
+/// Above this many tracked clients, full buckets are dropped: a client
+/// without a bucket starts with a full one anyway.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Which budget a route draws from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateBudget {
+    Read,
+    Refresh,
+    /// Failed authentications, keyed by address
+    AuthFailure,
+}
+
+/// Outcome of taking a token, as reported in the `RateLimit-*` headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    /// Bucket capacity
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again
+    pub reset_secs: u64,
+    /// Seconds until a request would be allowed, or 0 if this one was
+    pub retry_after_secs: u64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// A token bucket per client, holding up to `burst` requests and refilled
+/// at `per_minute`.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    burst: u32,
+    refill_per_sec: f64,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            burst,
+            refill_per_sec: f64::from(per_minute) / 60.0,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Takes a token from `client`'s bucket if one is available.
+    pub fn check(&self, client: &str) -> RateLimitStatus {
+        self.update(client, true)
+    }
+
+    /// Reports whether `client` has a token left, without taking it.
+    pub fn peek(&self, client: &str) -> RateLimitStatus {
+        self.update(client, false)
+    }
+
+    fn update(&self, client: &str, take: bool) -> RateLimitStatus {
+        let now = Instant::now();
+        let capacity = f64::from(self.burst);
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + self.refill(now - bucket.updated_at) < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        bucket.tokens = (bucket.tokens + self.refill(now - bucket.updated_at)).min(capacity);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed && take {
+            bucket.tokens -= 1.0;
+        }
+
+        RateLimitStatus {
+            allowed,
+            limit: self.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs: self.secs_until(capacity - bucket.tokens),
+            retry_after_secs: if allowed {
+                0
+            } else {
+                self.secs_until(1.0 - bucket.tokens).max(1)
+            },
+        }
+    }
+
+    fn refill(&self, elapsed: Duration) -> f64 {
+        elapsed.as_secs_f64() * self.refill_per_sec
+    }
+
+    fn secs_until(&self, tokens: f64) -> u64 {
+        (tokens / self.refill_per_sec).ceil() as u64
+    }
+}
+
+/// Separate budgets for cheap reads and for refreshes, which call upstreams,
+/// plus one for failed authentications, which would otherwise go uncounted.
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+    pub read: RateLimiter,
+    pub refresh: RateLimiter,
+    pub auth_failure: RateLimiter,
+}
+
+impl RateLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            read: RateLimiter::new(
+                config.rate_limit_read_per_minute,
+                config.rate_limit_read_burst,
+            ),
+            refresh: RateLimiter::new(
+                config.rate_limit_refresh_per_minute,
+                config.rate_limit_refresh_burst,
+            ),
+            auth_failure: RateLimiter::new(
+                config.rate_limit_auth_failures_per_minute,
+                config.rate_limit_auth_failures_burst,
+            ),
+        }
+    }
+
+    pub fn get(&self, budget: RateBudget) -> &RateLimiter {
+        match budget {
+            RateBudget::Read => &self.read,
+            RateBudget::Refresh => &self.refresh,
+            RateBudget::AuthFailure => &self.auth_failure,
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{
+    models::{country::Country, responses::ExchangeRateResponse},
+    utils::{
+        clients::{EcbRatesClient, ExchangeApiClient},
+        config::Config,
+        http::HttpClients,
+        resilience::{CircuitBreakers, ResilientClient, RetryPolicy},
+        upstream_cache::UpstreamCache,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateProviderKind {
+    OpenErApi,
+    Ecb,
+    File,
+}
+
+/// A source of USD-based exchange rates.
+#[async_trait]
+pub trait ExchangeRateProvider: Send + Sync {
+    /// Recorded on each country row whose rate came from this provider
+    fn name(&self) -> &str;
+
+    async fn fetch_rates(&self) -> Result<ExchangeRateResponse>;
+}
+
+/// Rates loaded from a local file: either the open.er-api JSON shape
+/// (`{ "rates": { "NGN": 1600.23 } }`) or a CSV with `currency,rate` columns.
+pub struct FileRatesProvider {
+    path: PathBuf,
+}
+
+impl FileRatesProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CsvRate {
+    currency: String,
+    rate: String,
+}
+
+#[async_trait]
+impl ExchangeRateProvider for FileRatesProvider {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn fetch_rates(&self) -> Result<ExchangeRateResponse> {
+        let contents = tokio::fs::read_to_string(&self.path).await?;
+        // A local file carries no publication time, so its modification time stands in
+        let modified = tokio::fs::metadata(&self.path)
+            .await?
+            .modified()
+            .ok()
+            .map(|modified| DateTime::<Utc>::from(modified).timestamp());
+
+        let is_csv = self
+            .path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+
+        if !is_csv {
+            let mut response = serde_json::from_str::<ExchangeRateResponse>(&contents)?;
+            response.time_last_update_unix = response.time_last_update_unix.or(modified);
+
+            return Ok(response);
+        }
+
+        let mut rates = HashMap::new();
+        for record in csv::Reader::from_reader(contents.as_bytes()).deserialize::<CsvRate>() {
+            let record = record?;
+            rates.insert(
+                record.currency.trim().to_uppercase(),
+                BigDecimal::from_str(record.rate.trim())?,
+            );
+        }
+
+        Ok(ExchangeRateResponse {
+            rates,
+            time_last_update_unix: modified,
+            ..Default::default()
+        })
+    }
+}
+
+/// Rates merged from a provider chain, with the provider that served each one
+/// and when that provider published it.
+#[derive(Debug, Default)]
+pub struct ExchangeRates {
+    pub rates: HashMap<String, BigDecimal>,
+    pub providers: HashMap<String, String>,
+    pub as_of: HashMap<String, DateTime<Utc>>,
+    /// Active manual overrides, applied instead of `rates`
+    pub overrides: HashMap<String, BigDecimal>,
+}
+
+impl ExchangeRates {
+    /// The rates already stored on countries, for refreshes that keep them.
+    pub fn from_stored(countries: &[Country]) -> Self {
+        let mut stored = Self::default();
+
+        for country in countries {
+            let (Some(code), Some(rate)) = (&country.currency_code, &country.feed_exchange_rate)
+            else {
+                continue;
+            };
+
+            stored.rates.insert(code.clone(), rate.clone());
+            if let Some(provider) = &country.rate_provider {
+                stored.providers.insert(code.clone(), provider.clone());
+            }
+            if let Some(as_of) = country
+                .rate_as_of
+                .as_ref()
+                .and_then(|as_of| as_of.parse::<DateTime<Utc>>().ok())
+            {
+                stored.as_of.insert(code.clone(), as_of);
+            }
+        }
+
+        stored
+    }
+}
+
+/// Providers tried in order. Later providers are only asked when an earlier one
+/// fails or is missing some of the wanted currencies.
+pub struct RateProviderChain {
+    providers: Vec<Box<dyn ExchangeRateProvider>>,
+}
+
+impl RateProviderChain {
+    pub fn new(providers: Vec<Box<dyn ExchangeRateProvider>>) -> Self {
+        Self { providers }
+    }
+
+    pub fn from_config(
+        config: &Config,
+        http: &HttpClients,
+        breakers: &CircuitBreakers,
+        cache: &UpstreamCache,
+    ) -> Self {
+        let retry = RetryPolicy::from_config(config);
+
+        let providers = config
+            .exchange_rate_providers
+            .iter()
+            .map(|kind| -> Box<dyn ExchangeRateProvider> {
+                match kind {
+                    RateProviderKind::OpenErApi => Box::new(ExchangeApiClient::new(
+                        config.exchange_rates_api.clone(),
+                        ResilientClient::new(
+                            http.exchange_rates.clone(),
+                            retry,
+                            breakers.get("open_er_api"),
+                        ),
+                        cache.clone(),
+                    )),
+                    RateProviderKind::Ecb => Box::new(EcbRatesClient::new(
+                        config.ecb_rates_api.clone(),
+                        ResilientClient::new(http.default.clone(), retry, breakers.get("ecb")),
+                    )),
+                    RateProviderKind::File => Box::new(FileRatesProvider::new(
+                        config.exchange_rates_file.clone().unwrap_or_default(),
+                    )),
+                }
+            })
+            .collect();
+
+        Self::new(providers)
+    }
+
+    pub async fn fetch_rates(&self, wanted: &HashSet<String>) -> Result<ExchangeRates> {
+        let mut merged = ExchangeRates::default();
+        let mut any_succeeded = false;
+
+        for provider in &self.providers {
+            if any_succeeded && wanted.iter().all(|code| merged.rates.contains_key(code)) {
+                break;
+            }
+
+            match provider.fetch_rates().await {
+                Ok(response) => {
+                    any_succeeded = true;
+                    let as_of = response
+                        .time_last_update_unix
+                        .and_then(|unix| DateTime::from_timestamp(unix, 0))
+                        .unwrap_or_else(Utc::now);
+
+                    for (code, rate) in response.rates {
+                        if !merged.rates.contains_key(&code) {
+                            merged
+                                .providers
+                                .insert(code.clone(), provider.name().to_string());
+                            merged.as_of.insert(code.clone(), as_of);
+                            merged.rates.insert(code, rate);
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Exchange rate provider {} failed: {:?}", provider.name(), e);
+                }
+            }
+        }
+
+        if !any_succeeded {
+            return Err(anyhow!("All exchange rate providers failed"));
+        }
+
+        Ok(merged)
+    }
+}
@@ -0,0 +1,348 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rand::random_range;
+use reqwest::{
+    Client, Response, StatusCode,
+    header::{HeaderMap, HeaderValue, RETRY_AFTER},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    middleware::{REQUEST_ID_HEADER, current_request_context},
+    utils::config::Config,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_retries: config.upstream_max_retries,
+            base_delay: Duration::from_millis(config.upstream_retry_base_delay_ms),
+            max_delay: Duration::from_millis(config.upstream_retry_max_delay_ms),
+        }
+    }
+
+    /// Exponential backoff capped at `max_delay`, with the upper half jittered.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = exponential / 2;
+
+        half + half.mul_f64(random_range(0.0..=1.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UpstreamStatus {
+    pub name: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub open_until: Option<String>,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<DateTime<Utc>>,
+    trial_in_flight: bool,
+}
+
+/// Stops calling an upstream for `open_duration` after `failure_threshold`
+/// consecutive failures, then lets a single trial request through.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    name: String,
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Arc<Mutex<BreakerState>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: impl Into<String>, failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            name: name.into(),
+            failure_threshold,
+            open_duration,
+            state: Arc::new(Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                open_until: None,
+                trial_in_flight: false,
+            })),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a permit if a request may be sent now. The permit records the
+    /// request's outcome; dropping it unsettled counts as a failure, so a
+    /// cancelled half-open trial doesn't hold the breaker shut.
+    pub fn try_acquire(&self) -> Option<CircuitPermit<'_>> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.open_until {
+            None => {}
+            Some(until) if Utc::now() < until => return None,
+            Some(_) if state.trial_in_flight => return None,
+            Some(_) => state.trial_in_flight = true,
+        }
+
+        Some(CircuitPermit {
+            breaker: self,
+            settled: false,
+        })
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+        state.trial_in_flight = false;
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        state.trial_in_flight = false;
+
+        if state.consecutive_failures >= self.failure_threshold {
+            let open_duration = chrono::Duration::from_std(self.open_duration).unwrap_or_default();
+            state.open_until = Some(Utc::now() + open_duration);
+        }
+    }
+
+    pub fn status(&self) -> UpstreamStatus {
+        let state = self.state.lock().unwrap();
+
+        let circuit_state = match state.open_until {
+            None => CircuitState::Closed,
+            Some(until) if Utc::now() < until => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        };
+
+        UpstreamStatus {
+            name: self.name.clone(),
+            state: circuit_state,
+            consecutive_failures: state.consecutive_failures,
+            open_until: state
+                .open_until
+                .map(|until| until.to_rfc3339_opts(SecondsFormat::Millis, true)),
+        }
+    }
+}
+
+/// Leave to send one request through a `CircuitBreaker`.
+#[must_use]
+#[derive(Debug)]
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    settled: bool,
+}
+
+impl CircuitPermit<'_> {
+    pub fn success(mut self) {
+        self.settled = true;
+        self.breaker.record_success();
+    }
+
+    pub fn failure(mut self) {
+        self.settled = true;
+        self.breaker.record_failure();
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.breaker.record_failure();
+        }
+    }
+}
+
+/// One circuit breaker per upstream, shared across refreshes through `AppState`.
+#[derive(Debug, Clone)]
+pub struct CircuitBreakers {
+    failure_threshold: u32,
+    open_duration: Duration,
+    breakers: Arc<Mutex<BTreeMap<String, CircuitBreaker>>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_duration,
+            breakers: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            config.circuit_breaker_failure_threshold,
+            Duration::from_secs(config.circuit_breaker_open_secs),
+        )
+    }
+
+    pub fn get(&self, name: &str) -> CircuitBreaker {
+        self.breakers
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                CircuitBreaker::new(name, self.failure_threshold, self.open_duration)
+            })
+            .clone()
+    }
+
+    pub fn statuses(&self) -> Vec<UpstreamStatus> {
+        self.breakers
+            .lock()
+            .unwrap()
+            .values()
+            .map(CircuitBreaker::status)
+            .collect()
+    }
+}
+
+/// HTTP client for upstream APIs that retries transient failures and reports
+/// outcomes to the upstream's circuit breaker.
+pub struct ResilientClient {
+    client: Client,
+    retry: RetryPolicy,
+    breaker: CircuitBreaker,
+}
+
+impl ResilientClient {
+    pub fn new(client: Client, retry: RetryPolicy, breaker: CircuitBreaker) -> Self {
+        Self {
+            client,
+            retry,
+            breaker,
+        }
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Response> {
+        self.get_with_headers(url, HeaderMap::new()).await
+    }
+
+    /// Like `get`, but sends extra request headers and also returns
+    /// `304 Not Modified` responses to the caller.
+    pub async fn get_with_headers(&self, url: &str, headers: HeaderMap) -> Result<Response> {
+        let mut attempt = 0;
+
+        loop {
+            let Some(permit) = self.breaker.try_acquire() else {
+                return Err(anyhow!(
+                    "Circuit breaker for {} is open",
+                    self.breaker.name()
+                ));
+            };
+
+            let mut headers = headers.clone();
+            if let Some(context) = current_request_context()
+                && let Ok(id) = HeaderValue::from_str(&context.id)
+            {
+                headers.insert(REQUEST_ID_HEADER, id);
+            }
+
+            let request = self.client.get(url).headers(headers);
+
+            let (error, retry_after) = match request.send().await {
+                Ok(response)
+                    if response.status().is_success()
+                        || response.status() == StatusCode::NOT_MODIFIED =>
+                {
+                    permit.success();
+                    return Ok(response);
+                }
+                Ok(response) if is_retryable_status(response.status()) => {
+                    permit.failure();
+                    (
+                        anyhow!(
+                            "{} returned HTTP {}",
+                            self.breaker.name(),
+                            response.status()
+                        ),
+                        retry_after(&response),
+                    )
+                }
+                Ok(response) => {
+                    // The upstream is reachable, it just rejected this request
+                    permit.success();
+                    return Err(anyhow!(
+                        "{} returned HTTP {}",
+                        self.breaker.name(),
+                        response.status()
+                    ));
+                }
+                Err(e) => {
+                    permit.failure();
+                    if !(e.is_timeout() || e.is_connect() || e.is_request()) {
+                        return Err(e.into());
+                    }
+                    (e.into(), None)
+                }
+            };
+
+            if attempt >= self.retry.max_retries {
+                return Err(error);
+            }
+
+            let delay = match retry_after {
+                Some(delay) if delay > self.retry.max_delay => return Err(error),
+                Some(delay) => delay,
+                None => self.retry.backoff(attempt),
+            };
+
+            tracing::warn!(
+                "Retrying {} in {:?} after error: {}",
+                self.breaker.name(),
+                delay,
+                error
+            );
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parses `Retry-After` as either delay seconds or an HTTP date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
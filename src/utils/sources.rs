@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    models::responses::CountryResponse,
+    utils::{
+        clients::{CountriesApiClient, CountriesApiV3Client},
+        config::Config,
+        http::HttpClients,
+        resilience::{CircuitBreakers, ResilientClient, RetryPolicy},
+        upstream_cache::UpstreamCache,
+        validation::{ParsedCountries, parse_country_records},
+    },
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CountrySourceKind {
+    #[default]
+    RestcountriesV2,
+    RestcountriesV3,
+    File,
+}
+
+/// A source of country data, normalised to the restcountries v2 shape and
+/// validated record by record.
+#[async_trait]
+pub trait CountrySource: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Returns `None` when the upstream reports nothing changed since the last fetch.
+    async fn fetch_countries(&self) -> Result<Option<ParsedCountries>>;
+}
+
+/// Countries loaded from a local JSON file in the restcountries v2 layout, for
+/// air-gapped deployments and tests.
+pub struct FileCountrySource {
+    path: PathBuf,
+}
+
+impl FileCountrySource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl CountrySource for FileCountrySource {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn fetch_countries(&self) -> Result<Option<ParsedCountries>> {
+        let contents = tokio::fs::read_to_string(&self.path).await?;
+        let records = serde_json::from_str::<Vec<Value>>(&contents)?;
+
+        Ok(Some(parse_country_records::<CountryResponse>(
+            self.name(),
+            records,
+        )))
+    }
+}
+
+pub fn country_source_from_config(
+    config: &Config,
+    http: &HttpClients,
+    breakers: &CircuitBreakers,
+    cache: &UpstreamCache,
+) -> Box<dyn CountrySource> {
+    let retry = RetryPolicy::from_config(config);
+
+    match config.country_source {
+        CountrySourceKind::RestcountriesV2 => Box::new(CountriesApiClient::new(
+            config.rest_countries_api.clone(),
+            ResilientClient::new(
+                http.default.clone(),
+                retry,
+                breakers.get("restcountries_v2"),
+            ),
+            cache.clone(),
+        )),
+        CountrySourceKind::RestcountriesV3 => Box::new(CountriesApiV3Client::new(
+            config.rest_countries_api.clone(),
+            ResilientClient::new(
+                http.default.clone(),
+                retry,
+                breakers.get("restcountries_v3"),
+            ),
+            cache.clone(),
+        )),
+        CountrySourceKind::File => Box::new(FileCountrySource::new(
+            config.countries_file.clone().unwrap_or_default(),
+        )),
+    }
+}
@@ -1,73 +1,192 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use reqwest::Client;
 
 use crate::{
-    db::repositories::CountryRepository,
+    db::repositories::{AuditRepository, CountryRepository},
     models::{
+        audit::{AuditAction, NewAuditEvent},
         country::Country,
-        requests::CountryFilters,
-        responses::{CountryResponse, ExchangeRateResponse},
+        quarantine::NewQuarantinedRecord,
+        refresh::RefreshDiff,
+        requests::{CountryFilters, RefreshRequest},
+        responses::CountryResponse,
+    },
+    utils::{
+        countries::{
+            RATE_SOURCE_FEED, RATE_SOURCE_OVERRIDE, compute_refresh_diff, country_changes,
+            process_currency_and_gdp,
+        },
+        image::generate_summary_image,
+        rates::ExchangeRates,
     },
-    utils::{countries::process_currency_and_gdp, image::generate_summary_image},
 };
 
-pub async fn refresh_countries_task(
-    repository: CountryRepository,
+/// Matches upstream countries to their exchange rates and estimates GDP,
+/// producing the rows a refresh would store.
+pub fn build_countries(
     countries_data: Vec<CountryResponse>,
-    exchange_rate_data: ExchangeRateResponse,
+    exchange_rate_data: &ExchangeRates,
     timestamp: DateTime<Utc>,
-) -> Result<()> {
-    let countries = countries_data
+) -> Vec<Country> {
+    countries_data
         .into_iter()
         .map(|country_data| {
             let (currency_code, exchange_rate, estimated_gdp) = process_currency_and_gdp(
                 country_data.currencies.as_ref(),
                 country_data.population,
                 &exchange_rate_data.rates,
+                &exchange_rate_data.overrides,
             );
+            let rate_code = currency_code.as_ref().filter(|_| exchange_rate.is_some());
+            let feed_exchange_rate =
+                rate_code.and_then(|code| exchange_rate_data.rates.get(code).cloned());
+            let rate_source = rate_code.map(|code| {
+                if exchange_rate_data.overrides.contains_key(code) {
+                    RATE_SOURCE_OVERRIDE.to_string()
+                } else {
+                    RATE_SOURCE_FEED.to_string()
+                }
+            });
+            let rate_provider =
+                rate_code.and_then(|code| exchange_rate_data.providers.get(code).cloned());
+            let rate_as_of = rate_code
+                .and_then(|code| exchange_rate_data.as_of.get(code))
+                .map(|as_of| as_of.to_rfc3339_opts(chrono::SecondsFormat::Millis, true));
 
             Country {
                 id: 0,
                 name: country_data.name,
+                alpha2_code: country_data.alpha2_code,
+                alpha3_code: country_data.alpha3_code,
                 capital: country_data.capital,
                 region: country_data.region,
                 population: country_data.population,
                 currency_code,
                 exchange_rate,
+                rate_provider,
+                rate_as_of,
+                is_stale: false,
+                rate_source,
+                feed_exchange_rate,
                 estimated_gdp,
                 flag_url: country_data.flag,
                 last_refreshed_at: timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
             }
         })
-        .collect::<Vec<Country>>();
+        .collect()
+}
+
+/// Everything a refresh needs from the upstream APIs.
+pub struct UpstreamData {
+    pub countries: Vec<CountryResponse>,
+    pub rejected: Vec<NewQuarantinedRecord>,
+    /// False when `countries` was rebuilt from the stored rows (the country
+    /// source answered `304 Not Modified`, or only rates are being refreshed),
+    /// so only rows whose rate moved need writing
+    pub countries_modified: bool,
+    pub rates: ExchangeRates,
+}
+
+pub async fn refresh_countries_task(
+    repository: CountryRepository,
+    audit: AuditRepository,
+    data: UpstreamData,
+    selection: RefreshRequest,
+    timestamp: DateTime<Utc>,
+    rate_change_threshold: f64,
+    actor: Option<String>,
+) -> Result<RefreshDiff> {
+    let countries_modified = data.countries_modified;
+    let mut countries = build_countries(data.countries, &data.rates, timestamp);
 
     tracing::info!(
         "Processed {} countries, starting batch insert",
         countries.len()
     );
 
+    let existing = repository
+        .filter(&CountryFilters::default(), None)
+        .await?
+        .into_iter()
+        .filter(|country| selection.selects(&country.name, country.region.as_deref()))
+        .map(|country| (country.name.to_lowercase(), country))
+        .collect::<HashMap<String, Country>>();
+
+    let audit_events = countries
+        .iter()
+        .filter_map(|country| {
+            let previous = existing.get(&country.name.to_lowercase());
+            let changes = country_changes(previous, Some(country));
+
+            if changes.is_empty() {
+                return None;
+            }
+
+            Some(NewAuditEvent {
+                country_name: country.name.clone(),
+                action: match previous {
+                    Some(_) => AuditAction::Update,
+                    None => AuditAction::Create,
+                },
+                source: "refresh".to_string(),
+                actor: actor.clone(),
+                changes,
+            })
+        })
+        .collect::<Vec<NewAuditEvent>>();
+
+    let diff = compute_refresh_diff(&existing, &countries, rate_change_threshold);
+
+    if !countries_modified {
+        // The country source sent nothing new, so only rows whose rate moved
+        // need writing
+        countries.retain(|country| {
+            existing
+                .get(&country.name.to_lowercase())
+                .is_none_or(|previous| {
+                    previous.exchange_rate != country.exchange_rate
+                        || previous.rate_provider != country.rate_provider
+                        || previous.rate_as_of != country.rate_as_of
+                        || previous.rate_source != country.rate_source
+                        || previous.feed_exchange_rate != country.feed_exchange_rate
+                })
+        });
+
+        if countries.is_empty() {
+            tracing::info!("Upstream data unchanged, skipping country upsert");
+            return Ok(diff);
+        }
+    }
+
     let saved_count = repository.insert_or_update(&countries).await?;
 
     tracing::info!("Successfully saved {} countries", saved_count);
 
-    Ok(())
+    let audited_count = audit.record(&audit_events).await?;
+
+    tracing::info!("Recorded {} audit events", audited_count);
+
+    Ok(diff)
 }
 
 pub async fn generate_image_task(
     repository: CountryRepository,
+    client: &Client,
     last_refresh_time: DateTime<Utc>,
 ) -> Result<()> {
     let total = repository.count().await?;
 
     let filters = CountryFilters {
-        region: None,
-        currency: None,
-        sort: Some("desc".to_string()),
+        sort: Some("gdp_desc".to_string()),
+        ..Default::default()
     };
-    let all_countries = repository.filter(&filters).await?;
+    let all_countries = repository.filter(&filters, None).await?;
     let top_5: Vec<_> = all_countries.into_iter().take(5).collect();
 
-    generate_summary_image(total, top_5, last_refresh_time).await?;
+    generate_summary_image(client, total, top_5, last_refresh_time).await?;
 
     Ok(())
 }
@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    Response,
+    header::{ETAG, HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+
+use crate::models::responses::ExchangeRateResponse;
+
+/// Cache validators returned by an upstream, replayed on the next request so
+/// an unchanged payload comes back as `304 Not Modified`.
+#[derive(Debug, Clone, Default)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_response(response: &Response) -> Self {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    pub fn request_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Some(value) = self
+            .etag
+            .as_deref()
+            .and_then(|etag| HeaderValue::from_str(etag).ok())
+        {
+            headers.insert(IF_NONE_MATCH, value);
+        }
+        if let Some(value) = self
+            .last_modified
+            .as_deref()
+            .and_then(|date| HeaderValue::from_str(date).ok())
+        {
+            headers.insert(IF_MODIFIED_SINCE, value);
+        }
+
+        headers
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedRates {
+    response: ExchangeRateResponse,
+    next_update: DateTime<Utc>,
+}
+
+/// Per-upstream validators and not-yet-expired rates, shared across refreshes
+/// through `AppState`.
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamCache {
+    validators: Arc<Mutex<HashMap<String, Validators>>>,
+    rates: Arc<Mutex<HashMap<String, CachedRates>>>,
+}
+
+impl UpstreamCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn validators(&self, upstream: &str) -> Validators {
+        self.validators
+            .lock()
+            .unwrap()
+            .get(upstream)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn store_validators(&self, upstream: &str, validators: Validators) {
+        self.validators
+            .lock()
+            .unwrap()
+            .insert(upstream.to_string(), validators);
+    }
+
+    /// Rates from an earlier fetch, if the upstream said they would not change yet.
+    pub fn fresh_rates(&self, upstream: &str) -> Option<ExchangeRateResponse> {
+        self.rates
+            .lock()
+            .unwrap()
+            .get(upstream)
+            .filter(|cached| Utc::now() < cached.next_update)
+            .map(|cached| cached.response.clone())
+    }
+
+    pub fn store_rates(&self, upstream: &str, response: &ExchangeRateResponse) {
+        let Some(next_update) = response
+            .time_next_update_unix
+            .and_then(|unix| DateTime::from_timestamp(unix, 0))
+        else {
+            return;
+        };
+
+        self.rates.lock().unwrap().insert(
+            upstream.to_string(),
+            CachedRates {
+                response: response.clone(),
+                next_update,
+            },
+        );
+    }
+
+    /// Forgets everything, so the next refresh downloads full payloads again.
+    pub fn clear(&self) {
+        self.validators.lock().unwrap().clear();
+        self.rates.lock().unwrap().clear();
+    }
+}
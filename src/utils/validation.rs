@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::models::{quarantine::NewQuarantinedRecord, responses::CountryResponse};
+
+/// Anything above this is a data error rather than a real country.
+const MAX_POPULATION: i64 = 10_000_000_000;
+
+/// Upstream country records split into those safe to store and those to quarantine.
+#[derive(Debug, Default)]
+pub struct ParsedCountries {
+    pub countries: Vec<CountryResponse>,
+    pub rejected: Vec<NewQuarantinedRecord>,
+}
+
+/// Deserialises and validates each record on its own, so one bad record is
+/// quarantined instead of failing the whole refresh.
+pub fn parse_country_records<T>(source: &str, records: Vec<Value>) -> ParsedCountries
+where
+    T: DeserializeOwned + Into<CountryResponse>,
+{
+    let mut parsed = ParsedCountries::default();
+    let mut seen = HashSet::new();
+
+    for record in records {
+        let result = serde_json::from_value::<T>(record.clone())
+            .map_err(|e| format!("malformed record: {}", e))
+            .map(Into::into)
+            .and_then(|country| validate_country(&country).map(|_| country))
+            .and_then(|country| {
+                if seen.insert(country.name.trim().to_lowercase()) {
+                    Ok(country)
+                } else {
+                    Err("duplicate of an earlier record with the same name".to_string())
+                }
+            });
+
+        match result {
+            Ok(country) => parsed.countries.push(country),
+            Err(reason) => {
+                tracing::warn!("Quarantining record from {}: {}", source, reason);
+                parsed.rejected.push(NewQuarantinedRecord {
+                    source: source.to_string(),
+                    country_name: record_name(&record),
+                    reason,
+                    payload: record,
+                });
+            }
+        }
+    }
+
+    parsed
+}
+
+pub fn validate_country(country: &CountryResponse) -> Result<(), String> {
+    if country.name.trim().is_empty() {
+        return Err("name is required".to_string());
+    }
+
+    if country.population < 0 {
+        return Err(format!(
+            "population must not be negative, got {}",
+            country.population
+        ));
+    }
+
+    if country.population > MAX_POPULATION {
+        return Err(format!(
+            "population {} is implausibly large",
+            country.population
+        ));
+    }
+
+    for (code, len) in [(&country.alpha2_code, 2), (&country.alpha3_code, 3)] {
+        if let Some(code) = code
+            && !is_country_code(code, len)
+        {
+            return Err(format!(
+                "country code {:?} is not a {}-letter ISO 3166-1 code",
+                code, len
+            ));
+        }
+    }
+
+    let codes = country
+        .currencies
+        .iter()
+        .flatten()
+        .filter_map(|currency| currency.code.as_deref());
+
+    for code in codes {
+        if !is_currency_code(code) {
+            return Err(format!(
+                "currency code {:?} is not a 3-letter ISO 4217 code",
+                code
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `code` looks like an ISO 4217 code: three uppercase ASCII letters.
+pub fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// Whether `code` looks like an ISO 3166-1 code of `len` uppercase ASCII letters.
+pub fn is_country_code(code: &str, len: usize) -> bool {
+    code.len() == len && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// Best-effort name for a rejected record, in either the v2 or v3 layout.
+fn record_name(record: &Value) -> Option<String> {
+    let name = record.get("name")?;
+
+    name.as_str()
+        .or_else(|| name.get("common").and_then(Value::as_str))
+        .map(str::to_string)
+}
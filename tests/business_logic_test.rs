@@ -1,20 +1,60 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
-use currency_exchange_api::utils::countries::{calculate_gdp, process_currency_and_gdp};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use currency_exchange_api::{
+    api::build_router,
+    auth::{generate_api_key, hash_api_key},
+    error::{AppError, ErrorFormat},
+    extract::{QueryParams, ValidatedQuery},
+    jwt::JwtVerifier,
+    middleware::request_context,
+    models::{
+        api_key::Role,
+        country::{COUNTRY_FIELDS, Country, CountryFields},
+        requests::{CountryFilters, RefreshQuery, RefreshRequest, RefreshScope},
+        responses::{CountryResponse, CountryResponseV3, ExchangeRateResponse},
+        scope::Scope,
+        state::AppState,
+    },
+    utils::{
+        clients::{CountriesApiClient, ExchangeApiClient, parse_ecb_rates},
+        config::Config,
+        countries::{
+            apply_rate_overrides, calculate_gdp, compute_refresh_diff, country_changes,
+            flag_stale_rates, preview_refresh, process_currency_and_gdp, stored_country_response,
+        },
+        http::{HttpClients, parse_headers},
+        rate_limit::{RateLimiter, RateLimits},
+        rates::{ExchangeRateProvider, ExchangeRates, FileRatesProvider, RateProviderChain},
+        resilience::{CircuitBreaker, CircuitBreakers, CircuitState, ResilientClient, RetryPolicy},
+        sources::{CountrySource, FileCountrySource},
+        upstream_cache::UpstreamCache,
+        validation::parse_country_records,
+    },
+};
 
 #[test]
 fn test_gdp_calculation_random_range() {
     let population = 1000000i64;
-    let exchange_rate = 100.0;
+    let exchange_rate = BigDecimal::from(100);
 
     let mut results = Vec::new();
     for _ in 0..10 {
-        let gdp = calculate_gdp(population, exchange_rate);
+        let gdp = calculate_gdp(population, &exchange_rate);
         assert!(gdp.is_some());
         let value = gdp.unwrap();
 
-        let min_gdp = (population as f64 * 1000.0) / exchange_rate;
-        let max_gdp = (population as f64 * 2000.0) / exchange_rate;
+        let min_gdp = BigDecimal::from(population * 1000) / &exchange_rate;
+        let max_gdp = BigDecimal::from(population * 2000) / &exchange_rate;
 
         assert!(
             value >= min_gdp,
@@ -32,8 +72,8 @@ fn test_gdp_calculation_random_range() {
         results.push(value);
     }
 
-    let first = results[0];
-    let all_same = results.iter().all(|&x| x == first);
+    let first = &results[0];
+    let all_same = results.iter().all(|x| x == first);
     assert!(
         !all_same,
         "GDP calculation should produce different random values"
@@ -42,18 +82,29 @@ fn test_gdp_calculation_random_range() {
 
 #[test]
 fn test_gdp_calculation_zero_exchange_rate() {
-    let gdp = calculate_gdp(1000000, 0.0);
+    let gdp = calculate_gdp(1000000, &BigDecimal::zero());
     assert!(gdp.is_none());
 }
 
+#[test]
+fn test_gdp_calculation_keeps_tiny_rates_exact() {
+    let exchange_rate = BigDecimal::from_str("0.00000123").unwrap();
+
+    let gdp = calculate_gdp(1000000, &exchange_rate).unwrap();
+
+    assert_eq!(gdp.fractional_digit_count(), 2);
+    assert!(gdp >= BigDecimal::from_str("813008130081300.81").unwrap());
+    assert!(gdp <= BigDecimal::from_str("1626016260162601.63").unwrap());
+}
+
 #[test]
 fn test_currency_handling_empty_array() {
     let rates = HashMap::new();
-    let (code, rate, gdp) = process_currency_and_gdp(None, 1000000, &rates);
+    let (code, rate, gdp) = process_currency_and_gdp(None, 1000000, &rates, &HashMap::new());
 
     assert!(code.is_none());
     assert!(rate.is_none());
-    assert_eq!(gdp, Some(0.0));
+    assert_eq!(gdp, Some(BigDecimal::zero()));
 }
 
 #[test]
@@ -74,10 +125,11 @@ fn test_currency_handling_multiple_currencies() {
     ];
 
     let mut rates = HashMap::new();
-    rates.insert("NGN".to_string(), 1600.0);
-    rates.insert("USD".to_string(), 1.0);
+    rates.insert("NGN".to_string(), BigDecimal::from(1600));
+    rates.insert("USD".to_string(), BigDecimal::from(1));
 
-    let (code, rate, gdp) = process_currency_and_gdp(Some(&currencies), 1000000, &rates);
+    let (code, rate, gdp) =
+        process_currency_and_gdp(Some(&currencies), 1000000, &rates, &HashMap::new());
 
     assert_eq!(code, Some("NGN".to_string()));
     assert!(rate.is_some());
@@ -96,9 +148,2449 @@ fn test_currency_not_found_in_rates() {
 
     let rates = HashMap::new();
 
-    let (code, rate, gdp) = process_currency_and_gdp(Some(&currencies), 1000000, &rates);
+    let (code, rate, gdp) =
+        process_currency_and_gdp(Some(&currencies), 1000000, &rates, &HashMap::new());
 
     assert_eq!(code, Some("XYZ".to_string()));
     assert!(rate.is_none());
     assert!(gdp.is_none());
 }
+
+#[test]
+fn test_override_takes_priority_over_feed_rate() {
+    use currency_exchange_api::models::responses::Currency;
+
+    let currencies = vec![Currency {
+        code: Some("NGN".to_string()),
+        name: None,
+        symbol: None,
+    }];
+
+    let rates = HashMap::from([("NGN".to_string(), BigDecimal::from(1600))]);
+    let overrides = HashMap::from([("NGN".to_string(), BigDecimal::from(1500))]);
+
+    let (_, rate, _) = process_currency_and_gdp(Some(&currencies), 1000000, &rates, &overrides);
+
+    assert_eq!(rate, Some(BigDecimal::from(1500)));
+}
+
+fn sample_country() -> Country {
+    Country {
+        id: 1,
+        name: "Ghana".to_string(),
+        alpha2_code: Some("GH".to_string()),
+        alpha3_code: Some("GHA".to_string()),
+        capital: Some("Accra".to_string()),
+        region: Some("Africa".to_string()),
+        population: 31072940,
+        currency_code: Some("GHS".to_string()),
+        exchange_rate: Some(BigDecimal::from_str("15.34").unwrap()),
+        rate_provider: Some("open_er_api".to_string()),
+        rate_as_of: Some("2026-02-28T00:00:00.000Z".to_string()),
+        is_stale: false,
+        rate_source: Some("feed".to_string()),
+        feed_exchange_rate: Some(BigDecimal::from_str("15.34").unwrap()),
+        estimated_gdp: Some(BigDecimal::from(3000000000i64)),
+        flag_url: Some("https://flagcdn.com/gh.svg".to_string()),
+        last_refreshed_at: "2026-03-01T00:00:00.000Z".to_string(),
+    }
+}
+
+#[test]
+fn test_country_changes_only_reports_changed_fields() {
+    let old = sample_country();
+    let mut new = sample_country();
+    new.exchange_rate = Some(BigDecimal::from_str("15.9").unwrap());
+    new.estimated_gdp = Some(BigDecimal::from(2500000000i64));
+    new.last_refreshed_at = "2026-03-02T00:00:00.000Z".to_string();
+
+    let changes = country_changes(Some(&old), Some(&new));
+
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes["exchange_rate"]["old"], 15.34);
+    assert_eq!(changes["exchange_rate"]["new"], 15.9);
+}
+
+#[test]
+fn test_country_changes_ignore_decimal_scale() {
+    let mut stored = sample_country();
+    stored.exchange_rate = Some(BigDecimal::from_str("15.34000000").unwrap());
+    let fetched = sample_country();
+
+    assert!(country_changes(Some(&stored), Some(&fetched)).is_empty());
+}
+
+#[test]
+fn test_small_decimals_serialize_without_exponent() {
+    let mut country = sample_country();
+    country.exchange_rate = Some(BigDecimal::from_str("0.00000010").unwrap());
+
+    let body = serde_json::to_string(&country).unwrap();
+    assert!(body.contains(r#""exchange_rate":0.00000010"#), "{}", body);
+}
+
+#[test]
+fn test_country_changes_on_delete() {
+    let old = sample_country();
+
+    let changes = country_changes(Some(&old), None);
+
+    assert_eq!(changes["currency_code"]["old"], "GHS");
+    assert!(changes["currency_code"]["new"].is_null());
+    assert!(!changes.contains_key("estimated_gdp"));
+}
+
+#[test]
+fn test_refresh_diff_added_missing_upstream_and_changes() {
+    let mut ghana = sample_country();
+    let mut togo = sample_country();
+    togo.name = "Togo".to_string();
+
+    let existing = HashMap::from([
+        ("ghana".to_string(), ghana.clone()),
+        ("togo".to_string(), togo),
+    ]);
+
+    ghana.population += 1000;
+    ghana.exchange_rate = Some(BigDecimal::from_str("16.5").unwrap());
+    let mut benin = sample_country();
+    benin.name = "Benin".to_string();
+
+    let diff = compute_refresh_diff(&existing, &[ghana, benin], 5.0);
+
+    assert_eq!(diff.added, vec!["Benin".to_string()]);
+    assert_eq!(diff.missing_upstream, vec!["Togo".to_string()]);
+    assert_eq!(diff.population_changes.len(), 1);
+    assert_eq!(
+        diff.population_changes[0].new - diff.population_changes[0].old,
+        1000
+    );
+    assert!(diff.currency_changes.is_empty());
+    assert_eq!(diff.rate_changes.len(), 1);
+    assert!(diff.rate_changes[0].change_percent.unwrap() > 7.0);
+}
+
+#[test]
+fn test_refresh_diff_reports_rates_appearing_and_disappearing() {
+    let mut ghana = sample_country();
+    let mut togo = sample_country();
+    togo.name = "Togo".to_string();
+    togo.exchange_rate = None;
+
+    let existing = HashMap::from([
+        ("ghana".to_string(), ghana.clone()),
+        ("togo".to_string(), togo.clone()),
+    ]);
+
+    ghana.exchange_rate = None;
+    togo.exchange_rate = Some(BigDecimal::from(600));
+
+    let diff = compute_refresh_diff(&existing, &[ghana, togo], 1.0);
+
+    assert_eq!(diff.rate_changes.len(), 2);
+    let ghana = &diff.rate_changes[0];
+    assert_eq!(ghana.name, "Ghana");
+    assert!(ghana.old.is_some());
+    assert_eq!(ghana.new, None);
+    assert_eq!(ghana.change_percent, None);
+    let togo = &diff.rate_changes[1];
+    assert_eq!(togo.old, None);
+    assert_eq!(togo.new, Some(BigDecimal::from(600)));
+
+    let json = serde_json::to_value(&diff).unwrap();
+    assert!(json["rate_changes"][0]["new"].is_null());
+    assert!(json["missing_upstream"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_refresh_diffs_stored_before_the_rename_still_load() {
+    let diff: currency_exchange_api::models::refresh::RefreshDiff =
+        serde_json::from_value(serde_json::json!({
+            "added": [],
+            "removed": ["Togo"],
+            "population_changes": [],
+            "currency_changes": [],
+            "rate_changes": [
+                { "name": "Ghana", "currency_code": "GHS", "old": 15.34, "new": 16.5, "change_percent": 7.56 }
+            ]
+        }))
+        .unwrap();
+
+    assert_eq!(diff.missing_upstream, ["Togo"]);
+    assert_eq!(diff.rate_changes[0].change_percent, Some(7.56));
+}
+
+#[test]
+fn test_refresh_diff_ignores_rate_moves_below_threshold() {
+    let old = sample_country();
+    let existing = HashMap::from([("ghana".to_string(), old.clone())]);
+
+    let mut new = old;
+    new.exchange_rate = Some(BigDecimal::from_str("15.4").unwrap());
+
+    let diff = compute_refresh_diff(&existing, &[new], 1.0);
+
+    assert!(diff.rate_changes.is_empty());
+}
+
+#[test]
+fn test_exchange_rates_parse_without_rounding() {
+    use currency_exchange_api::models::responses::ExchangeRateResponse;
+
+    let response: ExchangeRateResponse =
+        serde_json::from_str(r#"{ "rates": { "BTC": 0.0000095123456789123 } }"#).unwrap();
+
+    assert_eq!(
+        response.rates["BTC"],
+        BigDecimal::from_str("0.0000095123456789123").unwrap()
+    );
+}
+
+#[test]
+fn test_ecb_rates_rebased_to_usd() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01" xmlns="http://www.ecb.int/vocabulary/2002-08-01/eurofxref">
+    <Cube>
+        <Cube time="2026-10-16">
+            <Cube currency="USD" rate="1.25"/>
+            <Cube currency="GBP" rate="0.85"/>
+        </Cube>
+    </Cube>
+</gesmes:Envelope>"#;
+
+    let response = parse_ecb_rates(xml).unwrap();
+
+    assert_eq!(response.rates["USD"], BigDecimal::from(1));
+    assert_eq!(response.rates["EUR"], BigDecimal::from_str("0.8").unwrap());
+    assert_eq!(response.rates["GBP"], BigDecimal::from_str("0.68").unwrap());
+    assert_eq!(
+        response.time_last_update_unix,
+        Some(
+            chrono::NaiveDate::from_ymd_opt(2026, 10, 16)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp()
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_file_rates_provider_reads_csv() {
+    let path = std::env::temp_dir().join("business_logic_test_rates.csv");
+    std::fs::write(&path, "currency,rate\nNGN,1600.23\nghs,15.34\n").unwrap();
+
+    let response = FileRatesProvider::new(&path).fetch_rates().await.unwrap();
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(
+        response.rates["NGN"],
+        BigDecimal::from_str("1600.23").unwrap()
+    );
+    assert_eq!(
+        response.rates["GHS"],
+        BigDecimal::from_str("15.34").unwrap()
+    );
+}
+
+struct StaticRates {
+    name: &'static str,
+    rates: Option<Vec<(&'static str, i64)>>,
+}
+
+#[async_trait]
+impl ExchangeRateProvider for StaticRates {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn fetch_rates(&self) -> anyhow::Result<ExchangeRateResponse> {
+        let rates = self
+            .rates
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("provider down"))?
+            .iter()
+            .map(|(code, rate)| (code.to_string(), BigDecimal::from(*rate)))
+            .collect();
+
+        Ok(ExchangeRateResponse {
+            rates,
+            ..Default::default()
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_rate_provider_chain_falls_back_per_currency() {
+    let chain = RateProviderChain::new(vec![
+        Box::new(StaticRates {
+            name: "down",
+            rates: None,
+        }),
+        Box::new(StaticRates {
+            name: "primary",
+            rates: Some(vec![("NGN", 1600)]),
+        }),
+        Box::new(StaticRates {
+            name: "secondary",
+            rates: Some(vec![("NGN", 1), ("GHS", 15)]),
+        }),
+    ]);
+
+    let wanted = ["NGN".to_string(), "GHS".to_string()].into_iter().collect();
+    let rates = chain.fetch_rates(&wanted).await.unwrap();
+
+    assert!(rates.as_of.contains_key("NGN") && rates.as_of.contains_key("GHS"));
+
+    assert_eq!(rates.rates["NGN"], BigDecimal::from(1600));
+    assert_eq!(rates.providers["NGN"], "primary");
+    assert_eq!(rates.providers["GHS"], "secondary");
+}
+
+#[tokio::test]
+async fn test_rate_provider_chain_fails_when_all_providers_fail() {
+    let chain = RateProviderChain::new(vec![Box::new(StaticRates {
+        name: "down",
+        rates: None,
+    })]);
+
+    assert!(chain.fetch_rates(&Default::default()).await.is_err());
+}
+
+#[test]
+fn test_restcountries_v3_converted_to_v2_shape() {
+    let country: CountryResponseV3 = serde_json::from_str(
+        r#"{
+            "name": { "common": "Nigeria", "official": "Federal Republic of Nigeria" },
+            "cca2": "NG",
+            "cca3": "NGA",
+            "capital": ["Abuja"],
+            "region": "Africa",
+            "population": 206139589,
+            "currencies": { "NGN": { "name": "Nigerian naira", "symbol": "₦" } },
+            "flags": { "png": "https://flagcdn.com/w320/ng.png", "svg": "https://flagcdn.com/ng.svg" }
+        }"#,
+    )
+    .unwrap();
+
+    let country = CountryResponse::from(country);
+
+    assert_eq!(country.name, "Nigeria");
+    assert_eq!(country.alpha2_code.as_deref(), Some("NG"));
+    assert_eq!(country.alpha3_code.as_deref(), Some("NGA"));
+    assert_eq!(country.capital.as_deref(), Some("Abuja"));
+    assert_eq!(country.flag.as_deref(), Some("https://flagcdn.com/ng.svg"));
+    let currencies = country.currencies.unwrap();
+    assert_eq!(currencies[0].code.as_deref(), Some("NGN"));
+    assert_eq!(currencies[0].symbol.as_deref(), Some("₦"));
+}
+
+#[tokio::test]
+async fn test_file_country_source_reads_bundled_sample() {
+    let source = FileCountrySource::new("assets/countries.sample.json");
+
+    let parsed = source.fetch_countries().await.unwrap().unwrap();
+    let countries = parsed.countries;
+
+    assert_eq!(countries.len(), 5);
+    assert_eq!(countries[0].name, "Nigeria");
+    assert_eq!(countries[0].alpha3_code.as_deref(), Some("NGA"));
+    assert!(countries.iter().any(|country| country.currencies.is_none()));
+}
+
+#[test]
+fn test_retry_backoff_grows_and_is_capped() {
+    let policy = RetryPolicy {
+        max_retries: 5,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+    };
+
+    for _ in 0..20 {
+        let first = policy.backoff(0);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+        let third = policy.backoff(2);
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+
+        let capped = policy.backoff(10);
+        assert!(capped >= Duration::from_millis(500) && capped <= Duration::from_millis(1000));
+    }
+}
+
+#[test]
+fn test_circuit_breaker_opens_after_threshold() {
+    let breaker = CircuitBreaker::new("test", 2, Duration::from_secs(60));
+
+    breaker.try_acquire().unwrap().failure();
+    assert_eq!(breaker.status().state, CircuitState::Closed);
+
+    breaker.try_acquire().unwrap().failure();
+    assert_eq!(breaker.status().state, CircuitState::Open);
+    assert!(breaker.try_acquire().is_none());
+}
+
+#[test]
+fn test_circuit_breaker_half_open_allows_single_trial() {
+    let breaker = CircuitBreaker::new("test", 1, Duration::ZERO);
+    breaker.record_failure();
+
+    assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+    let trial = breaker.try_acquire().unwrap();
+    assert!(breaker.try_acquire().is_none());
+
+    trial.success();
+    assert_eq!(breaker.status().state, CircuitState::Closed);
+    assert!(breaker.try_acquire().is_some());
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_releases_trial_when_request_is_dropped() {
+    use axum::routing::get;
+
+    let app = axum::Router::new().route(
+        "/",
+        get(|| async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            "ok"
+        }),
+    );
+    let url = serve_locally(app).await;
+
+    let breaker = CircuitBreaker::new("local", 1, Duration::ZERO);
+    breaker.record_failure();
+    let client = ResilientClient::new(
+        reqwest::Client::new(),
+        RetryPolicy {
+            max_retries: 0,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        },
+        breaker.clone(),
+    );
+
+    let dropped = tokio::time::timeout(Duration::from_millis(100), client.get(&url)).await;
+    assert!(dropped.is_err());
+
+    // The abandoned trial counts as a failure and frees the next trial
+    assert_eq!(breaker.status().consecutive_failures, 2);
+    assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+    assert!(breaker.try_acquire().is_some());
+}
+
+#[tokio::test]
+async fn test_resilient_client_retries_unavailable_upstream() {
+    use axum::{Router, http::StatusCode, response::IntoResponse, routing::get};
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let app = Router::new().route(
+        "/",
+        get(move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    (StatusCode::SERVICE_UNAVAILABLE, [("retry-after", "0")]).into_response()
+                } else {
+                    "ok".into_response()
+                }
+            }
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let breaker = CircuitBreaker::new("local", 5, Duration::from_secs(60));
+    let client = ResilientClient::new(
+        reqwest::Client::new(),
+        RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        },
+        breaker.clone(),
+    );
+
+    let response = client.get(&url).await.unwrap();
+
+    assert_eq!(response.text().await.unwrap(), "ok");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(breaker.status().consecutive_failures, 0);
+}
+
+fn test_client() -> ResilientClient {
+    ResilientClient::new(
+        reqwest::Client::new(),
+        RetryPolicy {
+            max_retries: 0,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        },
+        CircuitBreaker::new("local", 5, Duration::from_secs(60)),
+    )
+}
+
+async fn serve_locally(app: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    url
+}
+
+#[tokio::test]
+async fn test_countries_client_sends_etag_and_handles_not_modified() {
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        response::IntoResponse,
+        routing::get,
+    };
+
+    let app = axum::Router::new().route(
+        "/",
+        get(|headers: HeaderMap| async move {
+            if headers
+                .get("if-none-match")
+                .is_some_and(|etag| etag == "\"v1\"")
+            {
+                return StatusCode::NOT_MODIFIED.into_response();
+            }
+
+            (
+                [("etag", "\"v1\"")],
+                r#"[{ "name": "Ghana", "population": 31072940 }]"#,
+            )
+                .into_response()
+        }),
+    );
+    let url = serve_locally(app).await;
+
+    let cache = UpstreamCache::new();
+    let client = CountriesApiClient::new(url, test_client(), cache.clone());
+
+    let first = client.fetch_countries().await.unwrap().unwrap();
+    assert_eq!(first.countries[0].name, "Ghana");
+    assert_eq!(
+        cache.validators("restcountries_v2").etag.as_deref(),
+        Some("\"v1\"")
+    );
+
+    assert!(client.fetch_countries().await.unwrap().is_none());
+
+    cache.clear();
+    assert!(client.fetch_countries().await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_exchange_client_reuses_rates_until_next_update() {
+    use axum::routing::get;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let next_update = chrono::Utc::now().timestamp() + 3600;
+    let app = axum::Router::new().route(
+        "/",
+        get(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                format!(
+                    r#"{{ "rates": {{ "NGN": 1600.23 }}, "time_next_update_unix": {next_update} }}"#
+                )
+            }
+        }),
+    );
+    let url = serve_locally(app).await;
+
+    let client = ExchangeApiClient::new(url, test_client(), UpstreamCache::new());
+
+    let first = client.fetch_rates().await.unwrap();
+    let second = client.fetch_rates().await.unwrap();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(first.rates["NGN"], second.rates["NGN"]);
+    assert_eq!(second.time_next_update_unix, Some(next_update));
+}
+
+#[test]
+fn test_stored_country_response_keeps_currency() {
+    let response = stored_country_response(&sample_country());
+
+    assert_eq!(response.name, "Ghana");
+    assert_eq!(response.population, 31072940);
+    assert_eq!(response.currencies.unwrap()[0].code.as_deref(), Some("GHS"));
+}
+
+#[test]
+fn test_malformed_and_invalid_records_are_quarantined() {
+    let records = serde_json::from_str(
+        r#"[
+            { "name": "Ghana", "population": 31072940, "currencies": [{ "code": "GHS" }] },
+            { "capital": "Nowhere", "population": 10 },
+            { "name": "Negaland", "population": -5 },
+            { "name": "Oddcoin", "population": 10, "currencies": [{ "code": "odd" }] },
+            { "name": "ghana", "population": 1 },
+            { "name": "Codeland", "alpha2Code": "CDL", "population": 10 }
+        ]"#,
+    )
+    .unwrap();
+
+    let parsed = parse_country_records::<CountryResponse>("test", records);
+
+    assert_eq!(parsed.countries.len(), 1);
+    assert_eq!(parsed.countries[0].name, "Ghana");
+    assert_eq!(parsed.rejected.len(), 5);
+    assert!(parsed.rejected[0].reason.contains("missing field `name`"));
+    assert!(parsed.rejected[0].country_name.is_none());
+    assert_eq!(parsed.rejected[1].country_name.as_deref(), Some("Negaland"));
+    assert!(parsed.rejected[1].reason.contains("negative"));
+    assert!(parsed.rejected[2].reason.contains("ISO 4217"));
+    assert!(parsed.rejected[3].reason.contains("duplicate"));
+    assert_eq!(parsed.rejected[3].payload["population"], 1);
+    assert!(parsed.rejected[4].reason.contains("ISO 3166-1"));
+}
+
+#[test]
+fn test_v3_primary_currency_keeps_upstream_order() {
+    let records = serde_json::from_str(
+        r#"[{
+            "name": { "common": "Zimbabwe" },
+            "population": 14862927,
+            "currencies": {
+                "ZWL": { "name": "Zimbabwean dollar" },
+                "BWP": { "name": "Botswana pula" },
+                "USD": { "name": "United States dollar" }
+            }
+        }]"#,
+    )
+    .unwrap();
+
+    let parsed = parse_country_records::<CountryResponseV3>("test", records);
+
+    let codes = parsed.countries[0]
+        .currencies
+        .iter()
+        .flatten()
+        .filter_map(|currency| currency.code.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(codes, ["ZWL", "BWP", "USD"]);
+}
+
+#[test]
+fn test_v3_records_validated_after_conversion() {
+    let records = serde_json::from_str(
+        r#"[
+            { "name": { "common": "Nigeria" }, "population": 206139589 },
+            { "name": { "common": "Broken" }, "population": "many" }
+        ]"#,
+    )
+    .unwrap();
+
+    let parsed = parse_country_records::<CountryResponseV3>("test", records);
+
+    assert_eq!(parsed.countries[0].name, "Nigeria");
+    assert_eq!(parsed.rejected[0].country_name.as_deref(), Some("Broken"));
+}
+
+#[test]
+fn test_rates_older_than_threshold_flagged_stale() {
+    let fresh = sample_country();
+    let mut stale = sample_country();
+    stale.rate_as_of = Some("2026-02-20T00:00:00.000Z".to_string());
+    let mut no_rate = sample_country();
+    no_rate.rate_as_of = None;
+
+    let mut countries = [fresh, stale, no_rate];
+    let reference = "2026-03-01T00:00:00Z".parse().unwrap();
+
+    let any_stale = flag_stale_rates(&mut countries, chrono::Duration::hours(48), reference);
+
+    assert!(any_stale);
+    assert!(!countries[0].is_stale);
+    assert!(countries[1].is_stale);
+    assert!(!countries[2].is_stale);
+}
+
+#[test]
+fn test_refresh_preview_groups_and_samples() {
+    let ghana = sample_country();
+    let mut togo = sample_country();
+    togo.name = "Togo".to_string();
+    let mut mali = sample_country();
+    mali.name = "Mali".to_string();
+
+    let existing = HashMap::from([
+        ("ghana".to_string(), ghana.clone()),
+        ("togo".to_string(), togo.clone()),
+        ("mali".to_string(), mali),
+    ]);
+
+    let mut updated_ghana = ghana;
+    updated_ghana.population += 1;
+    let mut unchanged_togo = togo;
+    unchanged_togo.estimated_gdp = Some(BigDecimal::from(1));
+    let incoming = ["Benin", "Niger", "Chad"].map(|name| {
+        let mut country = sample_country();
+        country.name = name.to_string();
+        country
+    });
+
+    let mut all = vec![updated_ghana, unchanged_togo];
+    all.extend(incoming);
+
+    let preview = preview_refresh(&existing, &all, 2);
+
+    assert_eq!(preview.inserted.count, 3);
+    assert_eq!(preview.inserted.sample.len(), 2);
+    assert_eq!(preview.updated.count, 1);
+    assert_eq!(preview.updated.sample[0].population, 31072941);
+    assert_eq!(preview.unchanged, 1);
+    assert_eq!(preview.missing_upstream.count, 1);
+    assert_eq!(preview.missing_upstream.sample[0].name, "Mali");
+}
+
+#[test]
+fn test_refresh_request_selection() {
+    let everything = RefreshRequest::default();
+    assert_eq!(everything.scope, RefreshScope::All);
+    assert!(everything.selects("Ghana", None));
+
+    let request: RefreshRequest = serde_json::from_str(
+        r#"{ "scope": "rates_only", "countries": ["ghana"], "regions": ["europe"] }"#,
+    )
+    .unwrap();
+
+    assert_eq!(request.scope, RefreshScope::RatesOnly);
+    assert!(request.selects("Ghana", Some("Africa")));
+    assert!(request.selects("France", Some("Europe")));
+    assert!(!request.selects("Togo", Some("Africa")));
+    assert!(!request.selects("Atlantis", None));
+}
+
+#[test]
+fn test_exchange_rates_from_stored_countries() {
+    let ghana = sample_country();
+    let mut antarctica = sample_country();
+    antarctica.name = "Antarctica".to_string();
+    antarctica.currency_code = None;
+
+    let stored = ExchangeRates::from_stored(&[ghana, antarctica]);
+
+    assert_eq!(stored.rates.len(), 1);
+    assert_eq!(stored.rates["GHS"], BigDecimal::from_str("15.34").unwrap());
+    assert_eq!(stored.providers["GHS"], "open_er_api");
+    assert_eq!(
+        stored.as_of["GHS"],
+        "2026-02-28T00:00:00Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_apply_rate_overrides_pins_rate_and_rescales_gdp() {
+    let mut countries = vec![sample_country()];
+    let overrides = HashMap::from([("GHS".to_string(), BigDecimal::from_str("30.68").unwrap())]);
+
+    apply_rate_overrides(&mut countries, &overrides);
+
+    let country = &countries[0];
+    assert_eq!(
+        country.exchange_rate,
+        Some(BigDecimal::from_str("30.68").unwrap())
+    );
+    assert_eq!(country.rate_source.as_deref(), Some("override"));
+    assert_eq!(
+        country.feed_exchange_rate,
+        Some(BigDecimal::from_str("15.34").unwrap())
+    );
+    assert_eq!(
+        country.estimated_gdp,
+        Some(BigDecimal::from_str("1500000000.00").unwrap())
+    );
+}
+
+#[test]
+fn test_apply_rate_overrides_reverts_to_feed_rate_once_expired() {
+    let mut country = sample_country();
+    country.exchange_rate = Some(BigDecimal::from_str("30.68").unwrap());
+    country.rate_source = Some("override".to_string());
+    country.estimated_gdp = Some(BigDecimal::from(1500000000i64));
+    let mut countries = vec![country];
+
+    apply_rate_overrides(&mut countries, &HashMap::new());
+
+    let country = &countries[0];
+    assert_eq!(
+        country.exchange_rate,
+        Some(BigDecimal::from_str("15.34").unwrap())
+    );
+    assert_eq!(country.rate_source.as_deref(), Some("feed"));
+    assert_eq!(
+        country.estimated_gdp,
+        Some(BigDecimal::from_str("3000000000.00").unwrap())
+    );
+}
+
+#[test]
+fn test_overridden_rates_are_never_stale() {
+    let mut country = sample_country();
+    country.rate_source = Some("override".to_string());
+    let mut countries = vec![country];
+
+    let reference = "2026-03-10T00:00:00Z".parse().unwrap();
+    let any_stale = flag_stale_rates(&mut countries, chrono::Duration::hours(48), reference);
+
+    assert!(!any_stale);
+    assert!(!countries[0].is_stale);
+}
+
+fn config_from(vars: &[(&str, &str)]) -> Config {
+    let required = [
+        ("DATABASE_URL", "mysql://localhost/test"),
+        ("DATABASE_MAX_CONNECTIONS", "1"),
+        ("DATABASE_CONNECTION_TIMEOUT", "1"),
+        ("SERVER_HOST", "127.0.0.1"),
+        ("SERVER_PORT", "0"),
+        ("REST_COUNTRIES_API", "http://localhost/countries"),
+        ("EXCHANGE_RATES_API", "http://localhost/rates"),
+    ];
+
+    envy::from_iter(
+        required
+            .iter()
+            .chain(vars)
+            .map(|(key, value)| (key.to_string(), value.to_string())),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_parse_upstream_headers() {
+    let headers = parse_headers("X-Team: payments\n X-Env:prod \r\n\nAccept: a, b").unwrap();
+
+    assert_eq!(headers["x-team"], "payments");
+    assert_eq!(headers["x-env"], "prod");
+    // Commas belong to the value
+    assert_eq!(headers["accept"], "a, b");
+
+    assert!(parse_headers("no separator").is_err());
+    assert!(parse_headers("Bad Name: value").is_err());
+}
+
+#[tokio::test]
+async fn test_http_clients_send_configured_headers() {
+    use axum::{http::HeaderMap, routing::get};
+
+    let app = axum::Router::new().route(
+        "/",
+        get(|headers: HeaderMap| async move {
+            let header = |name: &str| {
+                headers
+                    .get(name)
+                    .map(|value| value.to_str().unwrap().to_string())
+            };
+            axum::Json(serde_json::json!({
+                "user_agent": header("user-agent"),
+                "team": header("x-team"),
+                "api_key": header("apikey"),
+            }))
+        }),
+    );
+    let url = serve_locally(app).await;
+
+    let config = config_from(&[
+        ("UPSTREAM_HEADERS", "X-Team: payments"),
+        ("UPSTREAM_USER_AGENT", "rates-bot/1.0"),
+        ("EXCHANGE_RATES_API_KEY", "secret"),
+        ("EXCHANGE_RATES_API_KEY_HEADER", "apikey"),
+    ]);
+    let http = HttpClients::from_config(&config).unwrap();
+
+    let sent: serde_json::Value = http
+        .exchange_rates
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(sent["user_agent"], "rates-bot/1.0");
+    assert_eq!(sent["team"], "payments");
+    assert_eq!(sent["api_key"], "secret");
+
+    // The API key is only for the exchange rates provider
+    let sent: serde_json::Value = http
+        .default
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(sent["team"], "payments");
+    assert!(sent["api_key"].is_null());
+
+    // Upstream headers may carry provider credentials, so the identity
+    // provider gets none of them
+    let sent: serde_json::Value = http
+        .jwks
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(sent["user_agent"], "rates-bot/1.0");
+    assert!(sent["team"].is_null());
+    assert!(sent["api_key"].is_null());
+}
+
+#[test]
+fn test_http_clients_reject_bad_settings() {
+    let config = config_from(&[("UPSTREAM_CA_BUNDLE", "/nonexistent/ca.pem")]);
+    assert!(HttpClients::from_config(&config).is_err());
+
+    let config = config_from(&[("UPSTREAM_PROXY", "not a url")]);
+    assert!(HttpClients::from_config(&config).is_err());
+}
+
+async fn error_response(
+    format: ErrorFormat,
+    headers: &[(&str, &str)],
+    error: fn() -> AppError,
+) -> (
+    axum::http::StatusCode,
+    axum::http::HeaderMap,
+    serde_json::Value,
+) {
+    use axum::{body::Body, http::Request, middleware::from_fn_with_state, routing::get};
+    use tower::ServiceExt;
+
+    let app = axum::Router::new()
+        .route("/", get(move || async move { Err::<(), _>(error()) }))
+        .layer(from_fn_with_state(format, request_context));
+
+    let mut request = Request::builder().uri("/");
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+
+    let response = app
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    (status, headers, serde_json::from_slice(&body).unwrap())
+}
+
+#[test]
+fn test_app_error_status_and_code() {
+    let cases = [
+        (
+            AppError::validation("as_of", "bad"),
+            400,
+            "validation_failed",
+        ),
+        (
+            AppError::NotFound("Country not found".to_string()),
+            404,
+            "not_found",
+        ),
+        (
+            AppError::Database(sqlx::Error::RowNotFound),
+            404,
+            "not_found",
+        ),
+        (
+            AppError::Database(sqlx::Error::PoolTimedOut),
+            503,
+            "database_unavailable",
+        ),
+        (
+            AppError::Database(sqlx::Error::Protocol("boom".to_string())),
+            500,
+            "database_error",
+        ),
+        (
+            AppError::UpstreamUnavailable("down".to_string()),
+            503,
+            "upstream_unavailable",
+        ),
+        (
+            AppError::Internal(anyhow::anyhow!("boom")),
+            500,
+            "internal_error",
+        ),
+    ];
+
+    for (error, status, code) in cases {
+        assert_eq!(error.status().as_u16(), status, "{:?}", error);
+        assert_eq!(error.code(), code, "{:?}", error);
+    }
+}
+
+#[tokio::test]
+async fn test_error_response_includes_code_and_request_id() {
+    let (status, headers, body) = error_response(ErrorFormat::Json, &[], || {
+        AppError::NotFound("Country not found".to_string())
+    })
+    .await;
+
+    assert_eq!(status, 404);
+    assert_eq!(body["error"], "Country not found");
+    assert_eq!(body["code"], "not_found");
+    assert_eq!(
+        body["request_id"],
+        headers["x-request-id"].to_str().unwrap()
+    );
+
+    // Internal details never reach the client
+    let (status, _, body) =
+        error_response(ErrorFormat::Json, &[("x-request-id", "abc-123")], || {
+            AppError::Internal(anyhow::anyhow!("secret connection string"))
+        })
+        .await;
+
+    assert_eq!(status, 500);
+    assert_eq!(body["error"], "Internal server error");
+    assert_eq!(body["request_id"], "abc-123");
+}
+
+#[tokio::test]
+async fn test_problem_json_errors() {
+    let (status, headers, body) = error_response(
+        ErrorFormat::Json,
+        &[("accept", "application/problem+json")],
+        || AppError::validation("since", "must be an RFC 3339 timestamp"),
+    )
+    .await;
+
+    assert_eq!(status, 400);
+    assert_eq!(headers["content-type"], "application/problem+json");
+    assert_eq!(body["status"], 400);
+    assert_eq!(body["title"], "Bad Request");
+    assert_eq!(body["detail"], "Validation failed");
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(body["details"]["since"], "must be an RFC 3339 timestamp");
+
+    let (_, headers, body) = error_response(ErrorFormat::Problem, &[], || {
+        AppError::NotFound("Refresh not found".to_string())
+    })
+    .await;
+
+    assert_eq!(headers["content-type"], "application/problem+json");
+    assert_eq!(body["type"], "about:blank");
+}
+
+async fn query_response<T: QueryParams + Send + 'static>(
+    uri: &str,
+) -> (axum::http::StatusCode, serde_json::Value) {
+    use axum::{body::Body, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    let app = axum::Router::new().route(
+        "/",
+        get(|ValidatedQuery(_): ValidatedQuery<T>| async { "ok" }),
+    );
+
+    let response = app
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn test_validated_query_accepts_valid_params() {
+    let (status, _) =
+        query_response::<CountryFilters>("/?region=africa&currency=NGN&sort=gdp_asc").await;
+    assert_eq!(status, 200);
+
+    let (status, _) = query_response::<RefreshQuery>("/?dry_run=true").await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn test_validated_query_reports_every_invalid_param() {
+    let (status, body) =
+        query_response::<CountryFilters>("/?region=Atlantis&currency=ngn&sort=name&page=2").await;
+
+    assert_eq!(status, 400);
+    assert_eq!(body["code"], "validation_failed");
+    let details = body["details"].as_object().unwrap();
+    assert_eq!(details.len(), 4);
+    assert!(details["region"].as_str().unwrap().contains("Africa"));
+    assert!(details["currency"].is_string());
+    assert!(details["sort"].as_str().unwrap().contains("gdp_desc"));
+    assert!(
+        details["page"]
+            .as_str()
+            .unwrap()
+            .starts_with("unknown parameter")
+    );
+}
+
+#[tokio::test]
+async fn test_validated_query_pins_type_errors_to_their_param() {
+    let (status, body) = query_response::<RefreshQuery>("/?dry_run=maybe").await;
+
+    assert_eq!(status, 400);
+    assert!(body["details"]["dry_run"].is_string());
+}
+
+/// App state over a pool that never connects, for requests that are answered
+/// before reaching the database.
+fn offline_state(config: Config) -> AppState {
+    use currency_exchange_api::db::repositories::{
+        ApiKeyRepository, AuditRepository, CountryRepository, QuarantineRepository,
+        RateOverrideRepository, RefreshRepository,
+    };
+
+    let pool = sqlx::mysql::MySqlPoolOptions::new()
+        .connect_lazy(&config.database_url)
+        .unwrap();
+
+    AppState {
+        repository: CountryRepository::new(pool.clone()),
+        audit: AuditRepository::new(pool.clone()),
+        refreshes: RefreshRepository::new(pool.clone()),
+        quarantine: QuarantineRepository::new(pool.clone()),
+        overrides: RateOverrideRepository::new(pool.clone()),
+        api_keys: ApiKeyRepository::new(pool),
+        jwt: None,
+        http: HttpClients::from_config(&config).unwrap(),
+        breakers: CircuitBreakers::from_config(&config),
+        upstream_cache: UpstreamCache::new(),
+        rate_limits: RateLimits::from_config(&config),
+        schema_version: None,
+        config,
+    }
+}
+
+#[test]
+fn test_generated_api_keys_are_stored_by_hash() {
+    let first = generate_api_key();
+    let second = generate_api_key();
+
+    assert_ne!(first.key, second.key);
+    assert!(first.key.starts_with(&first.prefix));
+    assert_eq!(first.hash, hash_api_key(&first.key));
+    assert_eq!(first.hash.len(), 64);
+    assert!(!first.hash.contains(&first.key));
+}
+
+#[test]
+fn test_admin_role_includes_reader() {
+    for scope in Role::Reader.scopes() {
+        assert!(Role::Admin.scopes().contains(scope));
+    }
+    assert!(!Role::Reader.scopes().contains(&Scope::CountriesWrite));
+    assert_eq!("admin".parse::<Role>().unwrap(), Role::Admin);
+    assert!("owner".parse::<Role>().is_err());
+}
+
+#[tokio::test]
+async fn test_requests_without_api_key_are_rejected_when_auth_enabled() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[("AUTH_ENABLED", "true")])));
+
+    for (method, uri) in [
+        ("GET", "/countries"),
+        ("DELETE", "/countries/ghana"),
+        ("POST", "/countries/refresh"),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 401, "{} {}", method, uri);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "unauthorized");
+    }
+
+    // The docs stay public
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api-docs/openapi.json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        spec["components"]["securitySchemes"]["api_key"]["name"],
+        "x-api-key"
+    );
+}
+
+const TEST_ISSUER: &str = "https://gateway.test";
+const TEST_AUDIENCE: &str = "currency-api";
+
+/// A locally generated key per supported algorithm, published as a JWKS.
+struct TestKeys {
+    rs256: ring::signature::RsaKeyPair,
+    es256: ring::signature::EcdsaKeyPair,
+    eddsa: ring::signature::Ed25519KeyPair,
+}
+
+impl TestKeys {
+    fn generate() -> Self {
+        use base64::{Engine, engine::general_purpose::STANDARD};
+        use ring::{
+            rand::SystemRandom,
+            signature::{
+                ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, Ed25519KeyPair, RsaKeyPair,
+            },
+        };
+
+        let rng = SystemRandom::new();
+
+        // ring can't generate RSA keys, so that one is a checked-in test key
+        let pem = include_str!("fixtures/jwt_rs256_key.pem")
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect::<String>();
+        let rs256 = RsaKeyPair::from_pkcs8(&STANDARD.decode(pem).unwrap()).unwrap();
+
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let es256 =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let eddsa = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+
+        Self {
+            rs256,
+            es256,
+            eddsa,
+        }
+    }
+
+    fn jwks(&self) -> serde_json::Value {
+        use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+        use ring::signature::{KeyPair, RsaPublicKeyComponents};
+
+        let b64 = |bytes: &[u8]| URL_SAFE_NO_PAD.encode(bytes);
+        let rsa = RsaPublicKeyComponents::<Vec<u8>>::from(self.rs256.public());
+        let point = self.es256.public_key().as_ref();
+
+        serde_json::json!({
+            "keys": [
+                { "kty": "RSA", "kid": "rs256", "alg": "RS256", "use": "sig", "n": b64(&rsa.n), "e": b64(&rsa.e) },
+                { "kty": "EC", "kid": "es256", "crv": "P-256", "x": b64(&point[1..33]), "y": b64(&point[33..]) },
+                { "kty": "OKP", "kid": "eddsa", "crv": "Ed25519", "x": b64(self.eddsa.public_key().as_ref()) },
+                { "kty": "RSA", "kid": "encryption", "use": "enc", "n": "AQAB", "e": "AQAB" }
+            ]
+        })
+    }
+
+    /// Signs `claims` with the key for `alg`, using its `kid`.
+    fn token(&self, alg: &str, claims: &serde_json::Value) -> String {
+        self.token_with_kid(alg, &alg.to_lowercase(), claims)
+    }
+
+    fn token_with_kid(&self, alg: &str, kid: &str, claims: &serde_json::Value) -> String {
+        use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+        use ring::{rand::SystemRandom, signature::RSA_PKCS1_SHA256};
+
+        let header = serde_json::json!({ "alg": alg, "kid": kid, "typ": "JWT" });
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+
+        let rng = SystemRandom::new();
+        let signature = match alg {
+            "RS256" => {
+                let mut signature = vec![0; self.rs256.public().modulus_len()];
+                self.rs256
+                    .sign(
+                        &RSA_PKCS1_SHA256,
+                        &rng,
+                        signing_input.as_bytes(),
+                        &mut signature,
+                    )
+                    .unwrap();
+                signature
+            }
+            "ES256" => self
+                .es256
+                .sign(&rng, signing_input.as_bytes())
+                .unwrap()
+                .as_ref()
+                .to_vec(),
+            _ => self.eddsa.sign(signing_input.as_bytes()).as_ref().to_vec(),
+        };
+
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature))
+    }
+}
+
+fn test_claims(scope: &str) -> serde_json::Value {
+    serde_json::json!({
+        "iss": TEST_ISSUER,
+        "aud": TEST_AUDIENCE,
+        "sub": "checkout-service",
+        "scope": scope,
+        "exp": chrono::Utc::now().timestamp() + 300,
+    })
+}
+
+/// Config accepting tokens signed by `keys`, with the JWKS written to a file.
+fn jwt_config(name: &str, keys: &TestKeys) -> Config {
+    let path = std::env::temp_dir().join(format!("business_logic_test_{}.jwks.json", name));
+    std::fs::write(&path, keys.jwks().to_string()).unwrap();
+
+    config_from(&[
+        ("AUTH_ENABLED", "true"),
+        ("JWKS_FILE", path.to_str().unwrap()),
+        ("JWT_ISSUER", TEST_ISSUER),
+        ("JWT_AUDIENCE", TEST_AUDIENCE),
+        ("JWT_LEEWAY_SECS", "30"),
+    ])
+}
+
+async fn jwt_verifier(name: &str, keys: &TestKeys) -> JwtVerifier {
+    let config = jwt_config(name, keys);
+    JwtVerifier::from_config(&config, &reqwest::Client::new())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_jwt_verifier_accepts_each_supported_algorithm() {
+    let keys = TestKeys::generate();
+    let verifier = jwt_verifier("algorithms", &keys).await;
+
+    for alg in ["RS256", "ES256", "EdDSA"] {
+        let claims = verifier
+            .verify(&keys.token(alg, &test_claims("countries:read")))
+            .await
+            .unwrap_or_else(|e| panic!("{}: {}", alg, e));
+
+        assert_eq!(claims["sub"], "checkout-service", "{}", alg);
+    }
+
+    // `aud` may be a list
+    let mut claims = test_claims("countries:read");
+    claims["aud"] = serde_json::json!(["billing", TEST_AUDIENCE]);
+    assert!(verifier.verify(&keys.token("ES256", &claims)).await.is_ok());
+
+    // Expired within the leeway
+    claims["exp"] = serde_json::json!(chrono::Utc::now().timestamp() - 10);
+    assert!(verifier.verify(&keys.token("ES256", &claims)).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_jwt_verifier_rejects_invalid_tokens() {
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+    let keys = TestKeys::generate();
+    let verifier = jwt_verifier("invalid", &keys).await;
+    let now = chrono::Utc::now().timestamp();
+
+    let with = |field: &str, value: serde_json::Value| {
+        let mut claims = test_claims("countries:read");
+        claims[field] = value;
+        claims
+    };
+
+    let valid = keys.token("EdDSA", &test_claims("countries:read"));
+    let (signing_input, _) = valid.rsplit_once('.').unwrap();
+    let other_keys = TestKeys::generate();
+    let forged = other_keys.token("EdDSA", &test_claims("countries:read"));
+    let (header, payload) = signing_input.split_once('.').unwrap();
+    let none_header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+
+    let cases = [
+        (
+            keys.token("ES256", &with("exp", (now - 120).into())),
+            "expired",
+        ),
+        (
+            keys.token("ES256", &with("nbf", (now + 120).into())),
+            "not valid yet",
+        ),
+        (
+            keys.token("ES256", &with("iss", "https://evil.test".into())),
+            "issuer",
+        ),
+        (
+            keys.token("ES256", &with("aud", "another-api".into())),
+            "audience",
+        ),
+        (
+            keys.token("RS256", &with("exp", serde_json::Value::Null)),
+            "expiry",
+        ),
+        (forged, "signature"),
+        (
+            keys.token_with_kid("ES256", "eddsa", &test_claims("")),
+            "signature",
+        ),
+        (
+            keys.token_with_kid("ES256", "unknown", &test_claims("")),
+            "signature",
+        ),
+        (
+            format!("{}.{}.", none_header, payload),
+            "unsupported algorithm",
+        ),
+        (format!("{}.{}", header, payload), "malformed"),
+        ("not-a-token".to_string(), "malformed"),
+    ];
+
+    for (token, reason) in cases {
+        let error = verifier.verify(&token).await.unwrap_err().to_string();
+        assert!(
+            error.contains(reason),
+            "expected {:?}, got {:?}",
+            reason,
+            error
+        );
+    }
+}
+
+#[test]
+fn test_claim_scopes() {
+    use currency_exchange_api::{jwt::claim_scopes, models::scope::Scope};
+
+    let claims = |value: serde_json::Value| value.as_object().unwrap().clone();
+
+    assert_eq!(
+        claim_scopes(&claims(
+            serde_json::json!({ "scope": "countries:read openid countries:write" })
+        )),
+        vec![Scope::CountriesRead, Scope::CountriesWrite]
+    );
+    assert_eq!(
+        claim_scopes(&claims(serde_json::json!({ "scp": ["rates:write"] }))),
+        vec![Scope::RatesWrite]
+    );
+    assert!(claim_scopes(&claims(serde_json::json!({}))).is_empty());
+    assert_eq!(Role::Reader.scopes(), &[Scope::CountriesRead]);
+    assert_eq!(Role::Admin.scopes().len(), Scope::ALL.len());
+}
+
+#[tokio::test]
+async fn test_bearer_tokens_are_checked_against_route_scopes() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let keys = TestKeys::generate();
+    let mut state = offline_state(jwt_config("routes", &keys));
+    state.jwt = Some(jwt_verifier("routes", &keys).await);
+    let app = build_router(state);
+
+    // A `400` from query validation means the request got past authentication
+    let cases = [
+        ("GET", "/countries?page=1", "countries:read", 400),
+        ("GET", "/countries?page=1", "countries:write", 403),
+        (
+            "POST",
+            "/countries/refresh?dry_run=maybe",
+            "countries:read",
+            403,
+        ),
+        (
+            "POST",
+            "/countries/refresh?dry_run=maybe",
+            "countries:refresh",
+            400,
+        ),
+        (
+            "DELETE",
+            "/countries/ghana",
+            "countries:read countries:refresh",
+            403,
+        ),
+        ("PUT", "/currencies/NGN/override", "countries:write", 403),
+    ];
+
+    for (method, uri, scope, expected) in cases {
+        let token = keys.token("RS256", &test_claims(scope));
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status(),
+            expected,
+            "{} {} with {}",
+            method,
+            uri,
+            scope
+        );
+    }
+
+    let mut claims = test_claims("countries:read");
+    claims.as_object_mut().unwrap().remove("sub");
+    for token in [keys.token("ES256", &claims), "garbage".to_string()] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/countries")
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 401);
+    }
+
+    // Without a JWKS, bearer tokens aren't accepted at all
+    let app = build_router(offline_state(config_from(&[("AUTH_ENABLED", "true")])));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/countries?page=1")
+                .header(
+                    "authorization",
+                    format!(
+                        "Bearer {}",
+                        keys.token("RS256", &test_claims("countries:read"))
+                    ),
+                )
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[test]
+fn test_rate_limiter_allows_a_burst_then_refills() {
+    let limiter = RateLimiter::new(60, 3);
+
+    let statuses = (0..4)
+        .map(|_| limiter.check("ip:10.0.0.1"))
+        .collect::<Vec<_>>();
+
+    assert!(statuses[..3].iter().all(|status| status.allowed));
+    assert_eq!(statuses[0].limit, 3);
+    assert_eq!(statuses[0].remaining, 2);
+    assert_eq!(statuses[2].remaining, 0);
+    assert_eq!(statuses[2].retry_after_secs, 0);
+
+    // One token a second at 60 a minute
+    assert!(!statuses[3].allowed);
+    assert_eq!(statuses[3].retry_after_secs, 1);
+    assert_eq!(statuses[3].reset_secs, 3);
+
+    // Other clients have their own buckets
+    assert!(limiter.check("ip:10.0.0.2").allowed);
+}
+
+#[tokio::test]
+async fn test_rate_limited_requests_get_429_with_headers() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[
+        ("RATE_LIMIT_ENABLED", "true"),
+        ("RATE_LIMIT_READ_PER_MINUTE", "1"),
+        ("RATE_LIMIT_READ_BURST", "2"),
+        ("RATE_LIMIT_TRUST_FORWARDED_FOR", "true"),
+    ])));
+
+    let send = |method: &str, uri: &str, client: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("x-forwarded-for", format!("10.0.0.254, {}", client))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    // A `400` from query validation stands in for a served read
+    for remaining in ["1", "0"] {
+        let response = send("GET", "/countries?page=1", "203.0.113.7")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(response.headers()["ratelimit-limit"], "2");
+        assert_eq!(response.headers()["ratelimit-remaining"], remaining);
+    }
+
+    let response = send("GET", "/countries?page=1", "203.0.113.7")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["retry-after"], "60");
+    assert_eq!(response.headers()["ratelimit-remaining"], "0");
+    assert_eq!(response.headers()["ratelimit-reset"], "120");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "rate_limited");
+
+    // Refreshes draw from their own budget, and other clients from theirs
+    let response = send("POST", "/countries/refresh?dry_run=maybe", "203.0.113.7")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(response.headers()["ratelimit-limit"], "2");
+
+    let response = send("GET", "/countries?page=1", "198.51.100.1")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_spoofed_forwarded_for_entries_share_a_bucket() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[
+        ("RATE_LIMIT_ENABLED", "true"),
+        ("RATE_LIMIT_READ_PER_MINUTE", "1"),
+        ("RATE_LIMIT_READ_BURST", "2"),
+        ("RATE_LIMIT_TRUST_FORWARDED_FOR", "true"),
+    ])));
+
+    // A new forged leading entry each time, ahead of the one the proxy added
+    let mut statuses = Vec::new();
+    for forged in ["192.0.2.1", "192.0.2.2", "192.0.2.3"] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/countries?page=1")
+                    .header("x-forwarded-for", format!("{}, 203.0.113.7", forged))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        statuses.push(response.status().as_u16());
+    }
+    assert_eq!(statuses, [400, 400, 429]);
+}
+
+#[tokio::test]
+async fn test_failed_authentications_are_rate_limited_by_address() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[
+        ("AUTH_ENABLED", "true"),
+        ("RATE_LIMIT_ENABLED", "true"),
+        ("RATE_LIMIT_AUTH_FAILURES_PER_MINUTE", "1"),
+        ("RATE_LIMIT_AUTH_FAILURES_BURST", "2"),
+        ("RATE_LIMIT_TRUST_FORWARDED_FOR", "true"),
+    ])));
+
+    let send = |method: &str, uri: &str, client: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("x-forwarded-for", client)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    for (method, uri) in [("GET", "/countries"), ("DELETE", "/countries/ghana")] {
+        let response = send(method, uri, "203.0.113.7").await.unwrap();
+        assert_eq!(response.status(), 401);
+    }
+
+    // Every protected route shares the budget, and it is checked before the
+    // credentials are
+    for (method, uri) in [("GET", "/countries"), ("PUT", "/currencies/NGN/override")] {
+        let response = send(method, uri, "203.0.113.7").await.unwrap();
+        assert_eq!(response.status(), 429, "{} {}", method, uri);
+        assert_eq!(response.headers()["retry-after"], "60");
+        assert_eq!(response.headers()["ratelimit-limit"], "2");
+    }
+
+    let response = send("GET", "/countries", "198.51.100.1").await.unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[test]
+fn test_response_validators_match_conditional_headers() {
+    use axum::http::{HeaderMap, HeaderValue};
+    use currency_exchange_api::utils::http_cache::ResponseValidators;
+
+    let refreshed_at = "2026-10-18T09:30:00Z".parse().unwrap();
+    let validators = ResponseValidators::from_body(b"[]", Some(refreshed_at));
+    assert_eq!(
+        validators.etag,
+        ResponseValidators::from_body(b"[]", None).etag
+    );
+    assert_ne!(
+        validators.etag,
+        ResponseValidators::from_body(b"[{}]", None).etag
+    );
+
+    let headers = |pairs: &[(&'static str, &str)]| {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    };
+
+    assert!(!validators.matches(&headers(&[])));
+    assert!(validators.matches(&headers(&[("if-none-match", &validators.etag)])));
+    assert!(validators.matches(&headers(&[(
+        "if-none-match",
+        &format!("\"other\", W/{}", validators.etag)
+    )])));
+    assert!(validators.matches(&headers(&[("if-none-match", "*")])));
+    assert!(!validators.matches(&headers(&[("if-none-match", "\"other\"")])));
+
+    assert!(validators.matches(&headers(&[(
+        "if-modified-since",
+        "Sun, 18 Oct 2026 09:30:00 GMT"
+    )])));
+    assert!(!validators.matches(&headers(&[(
+        "if-modified-since",
+        "Sun, 18 Oct 2026 09:29:59 GMT"
+    )])));
+
+    // If-None-Match takes precedence over If-Modified-Since
+    assert!(!validators.matches(&headers(&[
+        ("if-none-match", "\"other\""),
+        ("if-modified-since", "Sun, 18 Oct 2026 10:00:00 GMT"),
+    ])));
+
+    // Without Last-Modified only the ETag can match
+    let validators = ResponseValidators::from_body(b"[]", None);
+    assert!(!validators.matches(&headers(&[(
+        "if-modified-since",
+        "Sun, 18 Oct 2026 10:00:00 GMT"
+    )])));
+}
+
+#[test]
+fn test_cache_control_lasts_until_the_next_refresh() {
+    use currency_exchange_api::utils::http_cache::cache_control;
+
+    let config = config_from(&[("REFRESH_INTERVAL_SECS", "600")]);
+    let now = chrono::Utc::now();
+
+    let max_age = |value: axum::http::HeaderValue| {
+        value
+            .to_str()
+            .unwrap()
+            .strip_prefix("public, max-age=")
+            .unwrap()
+            .parse::<i64>()
+            .unwrap()
+    };
+
+    let fresh = max_age(cache_control(
+        &config,
+        Some(now - chrono::Duration::seconds(100)),
+        None,
+    ));
+    assert!((495..=500).contains(&fresh), "{}", fresh);
+    assert_eq!(
+        max_age(cache_control(
+            &config,
+            Some(now - chrono::Duration::hours(1)),
+            None
+        )),
+        0
+    );
+    assert_eq!(max_age(cache_control(&config, None, None)), 0);
+
+    // An override expiring before the next refresh cuts it short
+    let capped = max_age(cache_control(
+        &config,
+        Some(now - chrono::Duration::seconds(100)),
+        Some(now + chrono::Duration::seconds(60)),
+    ));
+    assert!((55..=60).contains(&capped), "{}", capped);
+    let uncapped = max_age(cache_control(
+        &config,
+        Some(now - chrono::Duration::seconds(100)),
+        Some(now + chrono::Duration::hours(1)),
+    ));
+    assert!((495..=500).contains(&uncapped), "{}", uncapped);
+
+    let config = config_from(&[("AUTH_ENABLED", "true")]);
+    assert!(
+        cache_control(&config, Some(now), None)
+            .to_str()
+            .unwrap()
+            .starts_with("private, max-age=")
+    );
+}
+
+#[test]
+fn test_country_cache_counts_hits_and_drops_racing_loads() {
+    use currency_exchange_api::db::cache::{CountryCache, QueryKey};
+
+    let cache = CountryCache::new(Duration::from_secs(60));
+    let africa = QueryKey::new(&CountryFilters {
+        region: Some("Africa".to_string()),
+        ..Default::default()
+    });
+
+    assert!(cache.snapshot().is_none());
+    let generation = cache.generation();
+    cache.store_snapshot(generation, vec![sample_country()]);
+    cache.store_query(generation, africa.clone(), vec![sample_country()]);
+
+    assert_eq!(cache.snapshot().unwrap().len(), 1);
+    assert_eq!(cache.query(&africa).unwrap()[0].name, "Ghana");
+    // Region and currency match case-insensitively, like the SQL
+    assert!(
+        cache
+            .query(&QueryKey::new(&CountryFilters {
+                region: Some("africa".to_string()),
+                ..Default::default()
+            }))
+            .is_some()
+    );
+    assert!(QueryKey::new(&CountryFilters::default()).is_unfiltered());
+    assert!(!africa.is_unfiltered());
+
+    // A load that started before a write must not repopulate the cache
+    let generation = cache.generation();
+    cache.invalidate();
+    cache.store_snapshot(generation, vec![sample_country()]);
+    assert!(cache.snapshot().is_none());
+    assert!(cache.query(&africa).is_none());
+
+    let stats = cache.stats();
+    assert_eq!(stats.hits, 3);
+    assert_eq!(stats.misses, 3);
+    assert_eq!(stats.invalidations, 1);
+    assert_eq!(stats.cached_queries, 0);
+
+    // Entries expire after the TTL
+    let cache = CountryCache::new(Duration::ZERO);
+    let generation = cache.generation();
+    cache.store_snapshot(generation, vec![sample_country()]);
+    assert!(cache.snapshot().is_none());
+}
+
+#[test]
+fn test_country_cache_drops_overrides_once_one_expires() {
+    use currency_exchange_api::{db::cache::CountryCache, models::rate_override::ActiveOverrides};
+
+    let cache = CountryCache::new(Duration::from_secs(60));
+    let now = chrono::Utc::now();
+    let expires_at = now + chrono::Duration::minutes(5);
+
+    let overrides = ActiveOverrides {
+        rates: HashMap::from([("GHS".to_string(), BigDecimal::from(30))]),
+        expires_at: HashMap::from([("GHS".to_string(), expires_at)]),
+    };
+
+    assert!(cache.overrides(now).is_none());
+    cache.store_overrides(cache.generation(), overrides.clone());
+    assert_eq!(
+        cache.overrides(now).unwrap().rates["GHS"],
+        BigDecimal::from(30)
+    );
+    assert!(cache.overrides(expires_at).is_none());
+
+    // Invalidation clears them with the countries, and a racing load is dropped
+    let generation = cache.generation();
+    cache.invalidate();
+    assert!(cache.overrides(now).is_none());
+    cache.store_overrides(generation, overrides);
+    assert!(cache.overrides(now).is_none());
+
+    // With none active, the empty set is cached until the TTL
+    cache.store_overrides(cache.generation(), ActiveOverrides::default());
+    assert!(cache.overrides(now).unwrap().rates.is_empty());
+}
+
+#[tokio::test]
+async fn test_large_responses_are_compressed_as_the_client_prefers() {
+    use axum::{body::Body, http::Request};
+    use std::io::Read;
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[])));
+    let spec = |encoding: Option<&str>| {
+        let mut request = Request::builder().uri("/api-docs/openapi.json");
+        if let Some(encoding) = encoding {
+            request = request.header("accept-encoding", encoding);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    let response = spec(Some("gzip")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    assert_eq!(response.headers()["vary"], "accept-encoding");
+    assert!(response.headers().contains_key("x-request-id"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    let decoded: serde_json::Value = serde_json::from_str(&decoded).unwrap();
+    assert!(decoded["paths"]["/countries"].is_object());
+
+    let response = spec(Some("br, gzip;q=0.8, deflate;q=0.5")).await.unwrap();
+    assert_eq!(response.headers()["content-encoding"], "br");
+
+    let response = spec(Some("gzip;q=0, deflate")).await.unwrap();
+    assert_eq!(response.headers()["content-encoding"], "deflate");
+
+    // A coding refused with q=0 isn't used, even alongside `*`
+    let response = spec(Some("*, gzip;q=0")).await.unwrap();
+    assert_ne!(
+        response
+            .headers()
+            .get("content-encoding")
+            .map(|value| value.to_str().unwrap()),
+        Some("gzip")
+    );
+
+    let response = spec(None).await.unwrap();
+    assert!(!response.headers().contains_key("content-encoding"));
+
+    // Small bodies, like errors, are sent as they are
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/countries?page=1")
+                .header("accept-encoding", "gzip")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert!(!response.headers().contains_key("content-encoding"));
+
+    let app = build_router(offline_state(config_from(&[(
+        "COMPRESSION_ENABLED",
+        "false",
+    )])));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api-docs/openapi.json")
+                .header("accept-encoding", "gzip")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(!response.headers().contains_key("content-encoding"));
+}
+
+#[tokio::test]
+async fn test_cors_allows_only_configured_origins() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[(
+        "CORS_ALLOWED_ORIGINS",
+        "https://app.example.com,https://admin.example.com",
+    )])));
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("OPTIONS")
+                .uri("/countries")
+                .header("origin", "https://app.example.com")
+                .header("access-control-request-method", "GET")
+                .header("access-control-request-headers", "x-api-key")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert!(
+        headers["access-control-allow-headers"]
+            .to_str()
+            .unwrap()
+            .contains("x-api-key")
+    );
+    assert_eq!(headers["access-control-max-age"], "3600");
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/countries?page=1")
+                .header("origin", "https://admin.example.com")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://admin.example.com"
+    );
+    assert!(
+        response.headers()["access-control-expose-headers"]
+            .to_str()
+            .unwrap()
+            .contains("x-request-id")
+    );
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/countries?page=1")
+                .header("origin", "https://evil.example.com")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(
+        !response
+            .headers()
+            .contains_key("access-control-allow-origin")
+    );
+
+    // Without origins configured, no CORS headers are sent
+    let app = build_router(offline_state(config_from(&[])));
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/countries?page=1")
+                .header("origin", "https://app.example.com")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(
+        !response
+            .headers()
+            .contains_key("access-control-allow-origin")
+    );
+}
+
+#[tokio::test]
+async fn test_oversized_bodies_are_rejected_with_413() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[("MAX_BODY_BYTES", "16")])));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/countries/refresh")
+                .header("content-type", "application/json")
+                .header("content-length", "64")
+                .body(Body::from(format!("{{\"codes\":\"{}\"}}", "x".repeat(52))))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 413);
+    assert!(response.headers().contains_key("x-request-id"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "payload_too_large");
+}
+
+#[tokio::test]
+async fn test_chunked_bodies_over_the_limit_are_rejected_with_413() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[("MAX_BODY_BYTES", "16")])));
+
+    // Sent without a Content-Length, as a chunked body would be, so the
+    // limit is only hit while reading
+    let body = r#"{"countries": ["Ghana", "Kenya", "Nigeria"]}"#;
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/countries/batch")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 413);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "payload_too_large");
+}
+
+#[tokio::test]
+async fn test_slow_requests_time_out_except_refreshes() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    // Accepts connections and never answers, like a stalled database or upstream
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            held.push(stream);
+        }
+    });
+
+    let mut config = config_from(&[("REQUEST_TIMEOUT_SECS", "1")]);
+    config.database_url = format!("mysql://{}/test", address);
+    config.rest_countries_api = format!("http://{}/countries", address);
+    config.exchange_rates_api = format!("http://{}/rates", address);
+    let app = build_router(offline_state(config));
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/countries")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    assert!(response.headers().contains_key("x-request-id"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "request_timeout");
+
+    let refresh = app.oneshot(
+        Request::builder()
+            .method("POST")
+            .uri("/countries/refresh?dry_run=true")
+            .body(Body::empty())
+            .unwrap(),
+    );
+    assert!(
+        tokio::time::timeout(Duration::from_secs(2), refresh)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_override_bodies_are_validation_errors() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[])));
+
+    for (content_type, body, field) in [
+        (None, r#"{"rate": 10}"#, "body"),
+        (Some("application/json"), r#"{"rate": "#, "body"),
+        (Some("application/json"), r#""10""#, "body"),
+        (Some("application/json"), r#"{"rate": true}"#, "rate"),
+        (Some("application/json"), r#"{"reason": 42}"#, "reason"),
+        (Some("application/json"), r#"{"rate": 0}"#, "rate"),
+        (Some("application/json"), r#"{"rate": -3.5}"#, "rate"),
+        (
+            Some("application/json"),
+            r#"{"rate": 1000000000000}"#,
+            "rate",
+        ),
+        (Some("application/json"), r#"{"rate": 1.123456789}"#, "rate"),
+        (
+            Some("application/json"),
+            r#"{"expires_at": "2038-01-19T03:14:08Z"}"#,
+            "expires_at",
+        ),
+        (
+            Some("application/json"),
+            r#"{"expires_at": "2020-01-01T00:00:00Z"}"#,
+            "expires_at",
+        ),
+    ] {
+        let mut request = Request::builder()
+            .method("PUT")
+            .uri("/currencies/NGN/override");
+        if let Some(content_type) = content_type {
+            request = request.header("content-type", content_type);
+        }
+
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 400, "{body}");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error["code"], "validation_failed");
+        assert!(error["details"][field].is_string(), "{body}: {error}");
+    }
+}
+
+#[tokio::test]
+async fn test_malformed_refresh_and_batch_bodies_are_validation_errors() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[])));
+
+    for (uri, body, field) in [
+        ("/countries/refresh", r#"{"scope": 1}"#, "scope"),
+        ("/countries/refresh", r#"{"countries": ["#, "body"),
+        ("/countries/refresh", r#"{"region": ["Africa"]}"#, "region"),
+        (
+            "/countries/refresh",
+            r#"{"regions": ["Afrika"]}"#,
+            "regions",
+        ),
+        ("/countries/batch", r#"{"countries": "Ghana"}"#, "countries"),
+        (
+            "/countries/batch",
+            r#"{"countries": ["Ghana", 7]}"#,
+            "countries[1]",
+        ),
+        ("/countries/batch", r#"{}"#, "countries"),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 400, "{body}");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error["code"], "validation_failed");
+        assert!(error["details"][field].is_string(), "{body}: {error}");
+    }
+}
+
+#[test]
+fn test_country_fields_select_columns_and_project() {
+    let fields = CountryFields::parse(" flag_url,name ,exchange_rate,").unwrap();
+    assert!(!fields.is_all());
+    assert!(CountryFields::default().is_all());
+
+    // Overrides rewrite exchange_rate from these, and responses are dated by
+    // last_refreshed_at
+    assert_eq!(
+        fields.columns(),
+        [
+            "name",
+            "currency_code",
+            "exchange_rate",
+            "rate_source",
+            "feed_exchange_rate",
+            "estimated_gdp",
+            "flag_url",
+            "last_refreshed_at",
+        ]
+    );
+    assert_eq!(
+        CountryFields::parse("capital").unwrap().columns(),
+        ["name", "capital", "last_refreshed_at"]
+    );
+    assert!(
+        CountryFields::parse("is_stale")
+            .unwrap()
+            .columns()
+            .contains(&"rate_as_of")
+    );
+
+    let body = serde_json::to_string(&fields.project(&sample_country())).unwrap();
+    assert_eq!(
+        body,
+        r#"{"name":"Ghana","exchange_rate":15.34,"flag_url":"https://flagcdn.com/gh.svg"}"#
+    );
+
+    assert!(
+        CountryFields::parse("name,gdp")
+            .unwrap_err()
+            .contains("\"gdp\"")
+    );
+    assert!(CountryFields::parse(" , ").is_err());
+}
+
+#[test]
+fn test_country_fields_match_country_schema() {
+    use utoipa::OpenApi;
+
+    let spec = serde_json::to_value(currency_exchange_api::api::ApiDoc::openapi()).unwrap();
+    let mut properties = spec["components"]["schemas"]["Country"]["properties"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut fields = COUNTRY_FIELDS.map(str::to_string).to_vec();
+
+    properties.sort();
+    fields.sort();
+    assert_eq!(properties, fields);
+}
+
+#[tokio::test]
+async fn test_unknown_sparse_fields_are_rejected() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[])));
+
+    for uri in [
+        "/countries?fields=name,gdp",
+        "/countries/ghana?fields=",
+        "/countries/ghana?fields=name,Name",
+    ] {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 400, "{}", uri);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["details"]["fields"].is_string(), "{}", uri);
+    }
+}
+
+#[test]
+fn test_country_matches_name_or_iso_code() {
+    let country = sample_country();
+
+    for key in ["Ghana", "ghana", " GHANA ", "GH", "gh", "GHA", "gha"] {
+        assert!(country.matches(key), "{}", key);
+    }
+    for key in ["Gha na", "G", "GHAN", "NG", ""] {
+        assert!(!country.matches(key), "{}", key);
+    }
+
+    // Rows stored before ISO codes were kept only match by name
+    let mut legacy = sample_country();
+    legacy.alpha2_code = None;
+    legacy.alpha3_code = None;
+    assert!(legacy.matches("ghana"));
+    assert!(!legacy.matches("GH"));
+}
+
+#[tokio::test]
+async fn test_invalid_batch_lookups_are_rejected() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let app = build_router(offline_state(config_from(&[])));
+    let too_many = (0..101).map(|i| format!("C{}", i)).collect::<Vec<_>>();
+
+    for body in [
+        serde_json::json!({ "countries": [] }),
+        serde_json::json!({}),
+        serde_json::json!({ "countries": ["Ghana", "  "] }),
+        serde_json::json!({ "countries": too_many }),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/countries/batch")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 400, "{}", body);
+        let response = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let response: serde_json::Value = serde_json::from_slice(&response).unwrap();
+        assert_eq!(response["code"], "validation_failed");
+        assert!(response["details"]["countries"].is_string());
+    }
+}
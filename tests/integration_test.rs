@@ -1,11 +1,36 @@
+use std::time::Duration;
+
 use tower::util::ServiceExt;
 
 use axum::{Router, body::Body, http::Request};
+use bigdecimal::BigDecimal;
+use chrono::Utc;
 use currency_exchange_api::{
     api::build_router,
-    db::{pool::create_pool, repositories::CountryRepository},
-    models::state::AppState,
-    utils::config::load_config,
+    auth::generate_api_key,
+    db::{
+        migrations::verify_schema,
+        pool::create_pool,
+        repositories::{
+            ApiKeyRepository, AuditRepository, CountryRepository, QuarantineRepository,
+            RateOverrideRepository, RefreshRepository,
+        },
+    },
+    jwt::JwtVerifier,
+    models::{
+        api_key::Role,
+        audit::{AuditAction, NewAuditEvent},
+        country::{Country, CountryFields},
+        requests::CountryFilters,
+        state::AppState,
+    },
+    utils::{
+        config::{Config, load_config},
+        http::HttpClients,
+        rate_limit::RateLimits,
+        resilience::CircuitBreakers,
+        upstream_cache::UpstreamCache,
+    },
 };
 use dotenvy::dotenv;
 use reqwest::StatusCode;
@@ -13,8 +38,13 @@ use serde_json::{Value, json};
 use sqlx::MySqlPool;
 
 async fn setup_test_app() -> (Router, MySqlPool) {
+    setup_test_app_with(|_| {}).await
+}
+
+async fn setup_test_app_with(configure: impl FnOnce(&mut Config)) -> (Router, MySqlPool) {
     dotenv().ok();
-    let config = load_config().expect("Failed to load config");
+    let mut config = load_config().expect("Failed to load config");
+    configure(&mut config);
 
     let pool = create_pool(
         &config.database_url,
@@ -29,8 +59,65 @@ async fn setup_test_app() -> (Router, MySqlPool) {
         .await
         .expect("Failed to clean database");
 
+    sqlx::query("DELETE FROM audit_events")
+        .execute(&pool)
+        .await
+        .expect("Failed to clean database");
+
+    sqlx::query("DELETE FROM country_history")
+        .execute(&pool)
+        .await
+        .expect("Failed to clean database");
+
+    sqlx::query("DELETE FROM refreshes")
+        .execute(&pool)
+        .await
+        .expect("Failed to clean database");
+
+    sqlx::query("DELETE FROM quarantined_records")
+        .execute(&pool)
+        .await
+        .expect("Failed to clean database");
+
+    sqlx::query("DELETE FROM api_keys")
+        .execute(&pool)
+        .await
+        .expect("Failed to clean database");
+
+    sqlx::query("DELETE FROM rate_overrides")
+        .execute(&pool)
+        .await
+        .expect("Failed to clean database");
+
+    let schema_version = verify_schema(&pool).await.expect("Failed to verify schema");
+
+    let http = HttpClients::from_config(&config).expect("Failed to build HTTP clients");
+    let jwt = JwtVerifier::from_config(&config, &http.jwks)
+        .await
+        .expect("Failed to load JWKS");
+    let breakers = CircuitBreakers::from_config(&config);
+    let rate_limits = RateLimits::from_config(&config);
     let repository = CountryRepository::new(pool.clone());
-    let state = AppState { repository, config };
+    let audit = AuditRepository::new(pool.clone());
+    let refreshes = RefreshRepository::new(pool.clone());
+    let quarantine = QuarantineRepository::new(pool.clone());
+    let overrides = RateOverrideRepository::new(pool.clone());
+    let api_keys = ApiKeyRepository::new(pool.clone());
+    let state = AppState {
+        repository,
+        audit,
+        refreshes,
+        quarantine,
+        overrides,
+        api_keys,
+        jwt,
+        config,
+        schema_version,
+        http,
+        breakers,
+        upstream_cache: UpstreamCache::new(),
+        rate_limits,
+    };
 
     let app = build_router(state);
 
@@ -55,6 +142,354 @@ async fn make_request(app: &mut Router, method: &str, path: &str) -> (StatusCode
     (status, json)
 }
 
+#[tokio::test]
+async fn test_stale_rates_flagged_with_warning_header() {
+    let (app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, region, population, currency_code, exchange_rate,
+            rate_as_of, last_refreshed_at)
+         VALUES
+            ('Nigeria', 'Africa', 206139589, 'NGN', 1600.23, NOW() - INTERVAL 30 DAY, NOW()),
+            ('Ghana', 'Africa', 31072940, 'GHS', 15.34, NOW(), NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let request = Request::builder()
+        .uri("/countries/nigeria")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert!(response.headers().contains_key("warning"));
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["is_stale"], true);
+    assert!(body["rate_as_of"].is_string());
+
+    let request = Request::builder()
+        .uri("/countries/ghana")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert!(!response.headers().contains_key("warning"));
+}
+
+#[tokio::test]
+async fn test_conditional_get_countries() {
+    let (app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, region, population, currency_code, last_refreshed_at)
+         VALUES ('Ghana', 'Africa', 31072940, 'GHS', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let get = |uri: &str, header: Option<(&str, &str)>| {
+        let mut request = Request::builder().uri(uri);
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    for uri in ["/countries", "/countries/ghana"] {
+        let response = get(uri, None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key("cache-control"));
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+        assert!(!response.headers().contains_key("last-modified"));
+
+        let response = get(uri, Some(("if-none-match", &etag))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()["etag"], etag.as_str());
+    }
+
+    let response = get("/countries", None).await.unwrap();
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+    sqlx::query("UPDATE countries SET population = population + 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let response = get("/countries", Some(("if-none-match", &etag)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_max_age_ends_when_an_applied_override_expires() {
+    let (app, pool) = setup_test_app_with(|config| config.refresh_interval_secs = 3600).await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, region, population, currency_code, exchange_rate,
+            feed_exchange_rate, last_refreshed_at)
+         VALUES ('Ghana', 'Africa', 31072940, 'GHS', 15.34, 15.34, NOW()),
+                ('Kenya', 'Africa', 53771296, 'KES', 129.5, 129.5, NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    RateOverrideRepository::new(pool.clone())
+        .upsert(
+            "GHS",
+            &BigDecimal::from(30),
+            "Parallel market rate",
+            Utc::now() + chrono::Duration::seconds(90),
+            &[],
+        )
+        .await
+        .unwrap();
+
+    let max_age = |uri: &str| {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            response.headers()["cache-control"]
+                .to_str()
+                .unwrap()
+                .strip_prefix("public, max-age=")
+                .unwrap()
+                .parse::<i64>()
+                .unwrap()
+        }
+    };
+
+    // Responses showing the override go stale when it expires
+    for uri in ["/countries", "/countries/ghana"] {
+        let seconds = max_age(uri).await;
+        assert!((80..=90).contains(&seconds), "{}: {}", uri, seconds);
+    }
+
+    // Ones it doesn't touch last until the next refresh
+    assert!(max_age("/countries/kenya").await > 3500);
+}
+
+#[tokio::test]
+async fn test_if_modified_since_does_not_hide_deletes() {
+    let (app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, region, population, currency_code, last_refreshed_at)
+         VALUES ('Ghana', 'Africa', 31072940, 'GHS', NOW()),
+                ('Kenya', 'Africa', 53771296, 'KES', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let get = |header: Option<(&str, &str)>| {
+        let mut request = Request::builder().uri("/countries");
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    let response = get(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri("/countries/kenya")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // No remaining row's timestamp moved, so only the ETag can tell
+    let since = (Utc::now() + chrono::Duration::hours(1))
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+    let response = get(Some(("if-modified-since", &since))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = get(Some(("if-none-match", &etag))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_country_cache_invalidated_by_writes() {
+    let (_app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, region, population, currency_code, last_refreshed_at)
+         VALUES ('Ghana', 'Africa', 31072940, 'GHS', NOW()),
+                ('Kenya', 'Africa', 53771296, 'KES', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let repository = CountryRepository::with_cache(pool.clone(), Duration::from_secs(60));
+    let africa = CountryFilters {
+        region: Some("Africa".to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(repository.count().await.unwrap(), 2);
+    assert_eq!(repository.filter(&africa, None).await.unwrap().len(), 2);
+
+    // Changes made behind the repository's back aren't seen until a write
+    sqlx::query("DELETE FROM countries WHERE name = 'Kenya'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(repository.count().await.unwrap(), 2);
+    assert!(repository.get_by_name("kenya").await.unwrap().is_some());
+    assert_eq!(repository.filter(&africa, None).await.unwrap().len(), 2);
+
+    let mut ghana = repository.get_by_name("ghana").await.unwrap().unwrap();
+    ghana.population += 1;
+    repository.insert_or_update(&[ghana]).await.unwrap();
+
+    assert_eq!(repository.count().await.unwrap(), 1);
+    assert!(repository.get_by_name("kenya").await.unwrap().is_none());
+    assert_eq!(
+        repository
+            .get_by_name("ghana")
+            .await
+            .unwrap()
+            .unwrap()
+            .population,
+        31072941
+    );
+
+    assert!(repository.delete_by_name("ghana", &[]).await.unwrap());
+    assert!(repository.filter(&africa, None).await.unwrap().is_empty());
+
+    let stats = repository.cache_stats().unwrap();
+    assert_eq!(stats.invalidations, 2);
+    assert!(stats.hits >= 5);
+    assert!(stats.misses >= 4);
+}
+
+#[tokio::test]
+async fn test_delete_is_rolled_back_when_its_audit_event_fails() {
+    let (_app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, region, population, currency_code, last_refreshed_at)
+         VALUES ('Ghana', 'Africa', 31072940, 'GHS', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let repository = CountryRepository::new(pool.clone());
+    let event = |country_name: String| NewAuditEvent {
+        country_name,
+        action: AuditAction::Delete,
+        source: "api".to_string(),
+        actor: Some("ops".to_string()),
+        changes: Default::default(),
+    };
+
+    // Too long for audit_events.country_name
+    assert!(
+        repository
+            .delete_by_name("ghana", &[event("G".repeat(300))])
+            .await
+            .is_err()
+    );
+    assert!(repository.get_by_name("ghana").await.unwrap().is_some());
+
+    assert!(
+        repository
+            .delete_by_name("ghana", &[event("Ghana".to_string())])
+            .await
+            .unwrap()
+    );
+    assert!(repository.get_by_name("ghana").await.unwrap().is_none());
+
+    let (events,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM audit_events WHERE country_name = 'Ghana'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(events, 1);
+
+    // Nothing to delete, so nothing is recorded
+    assert!(
+        !repository
+            .delete_by_name("ghana", &[event("Ghana".to_string())])
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_override_cache_invalidated_by_writes_and_expiry() {
+    let (_app, pool) = setup_test_app().await;
+
+    let repository = CountryRepository::with_cache(pool.clone(), Duration::from_secs(60));
+    let overrides = RateOverrideRepository::with_cache(pool.clone(), repository.cache().unwrap());
+    let expires_at = Utc::now() + chrono::Duration::seconds(2);
+
+    overrides
+        .upsert(
+            "GHS",
+            &BigDecimal::from(30),
+            "Parallel market rate",
+            expires_at,
+            &[],
+        )
+        .await
+        .unwrap();
+    assert_eq!(overrides.active_rates(Utc::now()).await.unwrap().len(), 1);
+
+    // Served from memory, so a change behind the repository's back is missed
+    sqlx::query("UPDATE rate_overrides SET rate = 31")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let rates = overrides.active_rates(Utc::now()).await.unwrap();
+    assert_eq!(rates["GHS"], BigDecimal::from(30));
+
+    // Writes through either repository clear it
+    overrides
+        .upsert(
+            "GHS",
+            &BigDecimal::from(32),
+            "Parallel market rate",
+            expires_at,
+            &[],
+        )
+        .await
+        .unwrap();
+    let rates = overrides.active_rates(Utc::now()).await.unwrap();
+    assert_eq!(rates["GHS"], BigDecimal::from(32));
+
+    // And it is reloaded once the override expires
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    assert!(overrides.active_rates(Utc::now()).await.unwrap().is_empty());
+
+    let stats = repository.cache_stats().unwrap();
+    assert_eq!(stats.invalidations, 2);
+    assert_eq!(stats.hits, 1);
+}
+
 #[tokio::test]
 async fn test_status_empty_database() {
     let (mut app, _pool) = setup_test_app().await;
@@ -64,6 +499,8 @@ async fn test_status_empty_database() {
     assert_eq!(status, StatusCode::OK);
     assert_eq!(body["total_countries"], 0);
     assert!(body["last_refreshed_at"].is_null());
+    assert!(body["schema_version"].is_i64());
+    assert_eq!(body["upstreams"], json!([]));
 }
 
 #[tokio::test]
@@ -223,6 +660,8 @@ async fn test_get_country_not_found() {
 
     assert_eq!(status, StatusCode::NOT_FOUND);
     assert_eq!(body["error"], "Country not found");
+    assert_eq!(body["code"], "not_found");
+    assert!(body["request_id"].is_string());
 }
 
 #[tokio::test]
@@ -353,3 +792,588 @@ async fn test_status_after_data_inserted() {
     assert_eq!(body["total_countries"], 2);
     assert!(body.get("last_refreshed_at").is_some());
 }
+
+#[tokio::test]
+async fn test_delete_country_records_audit_event() {
+    let (mut app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, population, currency_code, exchange_rate)
+         VALUES ('Ghana', 31072940, 'GHS', 15.34)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let (status, _body) = make_request(&mut app, "DELETE", "/countries/ghana").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, body) = make_request(&mut app, "GET", "/audit?country=Ghana").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let events = body.as_array().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["action"], "delete");
+    assert_eq!(events[0]["source"], "api");
+    assert_eq!(events[0]["changes"]["currency_code"]["old"], "GHS");
+    assert!(events[0]["changes"]["currency_code"]["new"].is_null());
+}
+
+#[tokio::test]
+async fn test_audit_invalid_since() {
+    let (mut app, _pool) = setup_test_app().await;
+
+    let (status, body) = make_request(&mut app, "GET", "/audit?since=yesterday").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "Validation failed");
+    assert!(body["details"]["since"].is_string());
+}
+
+#[tokio::test]
+async fn test_refresh_diff_not_found() {
+    let (mut app, _pool) = setup_test_app().await;
+
+    let (status, body) = make_request(&mut app, "GET", "/refreshes/999999/diff").await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "Refresh not found");
+}
+
+#[tokio::test]
+async fn test_refresh_diff_still_running() {
+    let (mut app, pool) = setup_test_app().await;
+
+    let result = sqlx::query("INSERT INTO refreshes (status) VALUES ('running')")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let path = format!("/refreshes/{}/diff", result.last_insert_id());
+    let (status, body) = make_request(&mut app, "GET", &path).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["details"]["status"], "running");
+}
+
+#[tokio::test]
+async fn test_refresh_quarantine_lists_rejected_records() {
+    let (mut app, pool) = setup_test_app().await;
+
+    let result = sqlx::query("INSERT INTO refreshes (status) VALUES ('completed')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let refresh_id = result.last_insert_id();
+
+    sqlx::query(
+        "INSERT INTO quarantined_records (refresh_id, source, country_name, reason, payload)
+         VALUES (?, 'restcountries_v2', 'Negaland', 'population must not be negative', ?)",
+    )
+    .bind(refresh_id)
+    .bind(sqlx::types::Json(
+        json!({ "name": "Negaland", "population": -5 }),
+    ))
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let path = format!("/refreshes/{}/quarantine", refresh_id);
+    let (status, body) = make_request(&mut app, "GET", &path).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["country_name"], "Negaland");
+    assert_eq!(body[0]["payload"]["population"], -5);
+
+    let (status, _) = make_request(&mut app, "GET", "/refreshes/999999/quarantine").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_countries_as_of() {
+    let (mut app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO country_history (country_id, name, region, population, currency_code,
+            exchange_rate, last_refreshed_at, valid_from, valid_to)
+         VALUES
+         (1, 'Ghana', 'Africa', 31072940, 'GHS', 12.5, '2026-02-01 00:00:00', '2026-02-01 00:00:00', '2026-03-15 00:00:00'),
+         (1, 'Ghana', 'Africa', 31072940, 'GHS', 15.34, '2026-03-15 00:00:00', '2026-03-15 00:00:00', NULL)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let (status, body) = make_request(
+        &mut app,
+        "GET",
+        "/countries/ghana?as_of=2026-03-01T00:00:00Z",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["exchange_rate"], 12.5);
+
+    let (status, body) =
+        make_request(&mut app, "GET", "/countries?as_of=2026-01-01T00:00:00Z").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.as_array().unwrap().is_empty());
+
+    let (status, body) = make_request(&mut app, "GET", "/status?as_of=2026-04-01T00:00:00Z").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total_countries"], 1);
+}
+
+#[tokio::test]
+async fn test_get_countries_invalid_as_of() {
+    let (mut app, _pool) = setup_test_app().await;
+
+    let (status, body) = make_request(&mut app, "GET", "/countries?as_of=last-week").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["details"]["as_of"].is_string());
+}
+
+#[tokio::test]
+async fn test_rate_override_applies_to_reads() {
+    let (mut app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, region, population, currency_code, exchange_rate,
+            feed_exchange_rate, rate_source, estimated_gdp, last_refreshed_at)
+         VALUES ('Ghana', 'Africa', 31072940, 'GHS', 15.34, 15.34, 'feed', 3000000000, NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/currencies/ghs/override")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            json!({
+                "rate": 30.68,
+                "reason": "Parallel market rate",
+                "expires_at": "2099-01-01T00:00:00Z"
+            })
+            .to_string(),
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (status, body) = make_request(&mut app, "GET", "/countries/ghana").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["exchange_rate"], 30.68);
+    assert_eq!(body["feed_exchange_rate"], 15.34);
+    assert_eq!(body["rate_source"], "override");
+
+    let (status, _) = make_request(&mut app, "DELETE", "/currencies/GHS/override").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, body) = make_request(&mut app, "GET", "/countries/ghana").await;
+    assert_eq!(body["exchange_rate"], 15.34);
+    assert_eq!(body["rate_source"], "feed");
+
+    // Setting and removing the override are both audited as rate changes
+    let (status, body) = make_request(&mut app, "GET", "/audit?country=Ghana").await;
+    assert_eq!(status, StatusCode::OK);
+    let events = body.as_array().unwrap();
+    assert_eq!(events.len(), 2);
+    for event in events {
+        assert_eq!(event["action"], "update");
+        assert_eq!(event["source"], "api");
+    }
+    assert_eq!(events[0]["changes"]["exchange_rate"]["old"], 15.34);
+    assert_eq!(events[0]["changes"]["exchange_rate"]["new"], 30.68);
+    assert_eq!(events[1]["changes"]["exchange_rate"]["old"], 30.68);
+    assert_eq!(events[1]["changes"]["exchange_rate"]["new"], 15.34);
+}
+
+#[tokio::test]
+async fn test_rate_override_validation() {
+    let (app, _pool) = setup_test_app().await;
+
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/currencies/naira/override")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            json!({ "rate": -1, "expires_at": "2020-01-01T00:00:00Z" }).to_string(),
+        ))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    for field in ["code", "rate", "reason", "expires_at"] {
+        assert!(body["details"][field].is_string(), "missing {field}");
+    }
+}
+
+#[tokio::test]
+async fn test_get_countries_invalid_filters() {
+    let (mut app, _pool) = setup_test_app().await;
+
+    let (status, body) = make_request(
+        &mut app,
+        "GET",
+        "/countries?currency=ngn&sort=population&limit=5",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "validation_failed");
+    assert!(body["details"]["currency"].is_string());
+    assert!(body["details"]["sort"].is_string());
+    assert!(body["details"]["limit"].is_string());
+}
+
+#[tokio::test]
+async fn test_batch_country_lookup() {
+    let (app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, alpha2_code, alpha3_code, region, population,
+            currency_code, last_refreshed_at)
+         VALUES ('Ghana', 'GH', 'GHA', 'Africa', 31072940, 'GHS', NOW()),
+                ('Kenya', 'KE', 'KEN', 'Africa', 53771296, 'KES', NOW()),
+                ('Nigeria', 'NG', 'NGA', 'Africa', 206139589, 'NGN', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/countries/batch")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            json!({ "countries": ["nga", "Ghana", "Atlantis", "GH", "ke", "XX"] }).to_string(),
+        ))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let names = body["countries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|country| country["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Nigeria", "Ghana", "Kenya"]);
+    assert_eq!(body["countries"][0]["alpha2_code"], "NG");
+    assert_eq!(body["missing"], json!(["Atlantis", "XX"]));
+}
+
+#[tokio::test]
+async fn test_get_by_names_or_codes_with_and_without_cache() {
+    let (_app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, alpha2_code, alpha3_code, region, population,
+            currency_code, last_refreshed_at)
+         VALUES ('Ghana', 'GH', 'GHA', 'Africa', 31072940, 'GHS', NOW()),
+                ('Kenya', 'KE', 'KEN', 'Africa', 53771296, 'KES', NOW()),
+                ('Nigeria', 'NG', 'NGA', 'Africa', 206139589, 'NGN', NOW()),
+                ('Togo', 'TG', 'TGO', 'Africa', 8278724, 'XOF', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let uncached = CountryRepository::new(pool.clone());
+    let cached = CountryRepository::with_cache(pool.clone(), Duration::from_secs(60));
+    let names = |countries: Vec<Country>| {
+        let mut names = countries
+            .into_iter()
+            .map(|country| country.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    // Names in any case, alpha-2 and alpha-3 codes, the same country more
+    // than once and keys that match nothing
+    let keys = [
+        "ghana", " GH ", "gha", "Ghana", "ke", "NGA", "nigeria", "Atlantis", "XX", "XXX", "",
+    ]
+    .map(str::to_string);
+
+    let found = uncached.get_by_names_or_codes(&keys).await.unwrap();
+    assert_eq!(names(found.clone()), ["Ghana", "Kenya", "Nigeria"]);
+    assert_eq!(found[0].alpha3_code.as_deref(), Some("GHA"));
+    assert_eq!(
+        names(cached.get_by_names_or_codes(&keys).await.unwrap()),
+        ["Ghana", "Kenya", "Nigeria"]
+    );
+
+    let nothing = ["Atlantis", "XX", "ZZZ"].map(str::to_string);
+    assert!(
+        uncached
+            .get_by_names_or_codes(&nothing)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        cached
+            .get_by_names_or_codes(&nothing)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        uncached
+            .get_by_names_or_codes(&[])
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_get_countries_sparse_fields() {
+    let (mut app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, capital, region, population, currency_code, exchange_rate,
+            feed_exchange_rate, estimated_gdp, flag_url, last_refreshed_at)
+         VALUES ('Ghana', 'Accra', 'Africa', 31072940, 'GHS', 15.34, 15.34, 3000000000,
+            'https://flagcdn.com/gh.svg', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let (status, body) = make_request(
+        &mut app,
+        "GET",
+        "/countries?fields=exchange_rate,name,flag_url",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let country = body[0].as_object().unwrap();
+    assert_eq!(
+        country.keys().collect::<Vec<_>>(),
+        ["name", "exchange_rate", "flag_url"]
+    );
+    assert_eq!(country["exchange_rate"], 15.34);
+
+    let (status, body) = make_request(&mut app, "GET", "/countries/ghana?fields=capital").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "capital": "Accra" }));
+
+    let (status, body) = make_request(
+        &mut app,
+        "GET",
+        "/countries/ghana?fields=name,population&as_of=2026-01-01T00:00:00Z",
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_sparse_fields_match_with_and_without_cache() {
+    let (_app, pool) = setup_test_app().await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, capital, region, population, currency_code, exchange_rate,
+            feed_exchange_rate, estimated_gdp, flag_url, last_refreshed_at)
+         VALUES ('Ghana', 'Accra', 'Africa', 31072940, 'GHS', 15.34, 15.34, 3000000000,
+            'https://flagcdn.com/gh.svg', NOW()),
+                ('Kenya', 'Nairobi', 'Africa', 53771296, 'KES', 129.5, 129.5, 2000000000,
+            'https://flagcdn.com/ke.svg', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // The uncached repository reads only the needed columns; the cached one
+    // serves whole rows from memory. Both must project to the same response.
+    let uncached = CountryRepository::new(pool.clone());
+    let cached = CountryRepository::with_cache(pool.clone(), Duration::from_secs(60));
+    let by_region = |region: Option<&str>| CountryFilters {
+        region: region.map(str::to_string),
+        ..Default::default()
+    };
+
+    for list in [
+        "exchange_rate,name,flag_url",
+        "capital",
+        "population,region",
+        "is_stale,currency_code",
+    ] {
+        let fields = CountryFields::parse(list).unwrap();
+        let project = |countries: &[Country]| {
+            countries
+                .iter()
+                .map(|country| serde_json::to_value(fields.project(country)).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        for filters in [by_region(None), by_region(Some("Africa"))] {
+            let narrow = uncached
+                .filter_fields(&filters, None, &fields)
+                .await
+                .unwrap();
+            let full = cached.filter_fields(&filters, None, &fields).await.unwrap();
+            assert_eq!(project(&narrow), project(&full), "fields={}", list);
+            assert_eq!(project(&narrow).len(), 2);
+        }
+
+        let narrow = uncached
+            .get_by_name_fields("ghana", None, &fields)
+            .await
+            .unwrap()
+            .unwrap();
+        let full = cached
+            .get_by_name_fields("ghana", None, &fields)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(project(&[narrow]), project(&[full]), "fields={}", list);
+    }
+
+    // Only the uncached read left out columns that weren't asked for
+    let fields = CountryFields::parse("population").unwrap();
+    let narrow = uncached
+        .get_by_name_fields("ghana", None, &fields)
+        .await
+        .unwrap()
+        .unwrap();
+    let full = cached
+        .get_by_name_fields("ghana", None, &fields)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(narrow.capital, None);
+    assert_eq!(full.capital.as_deref(), Some("Accra"));
+    assert!(cached.cache_stats().unwrap().hits > 0);
+}
+
+#[tokio::test]
+async fn test_api_keys_with_the_same_name_are_rate_limited_apart() {
+    let (app, pool) = setup_test_app_with(|config| {
+        config.auth_enabled = true;
+        config.rate_limit_enabled = true;
+        config.rate_limit_read_per_minute = 1;
+        config.rate_limit_read_burst = 1;
+    })
+    .await;
+
+    let api_keys = ApiKeyRepository::new(pool.clone());
+    let first = generate_api_key();
+    let second = generate_api_key();
+    for key in [&first, &second] {
+        api_keys
+            .create("dashboard", Role::Reader, &key.prefix, &key.hash)
+            .await
+            .unwrap();
+    }
+
+    let send = |key: &str| {
+        let request = Request::builder()
+            .uri("/countries")
+            .header("x-api-key", key)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+
+    assert_eq!(send(&first.key).await.unwrap().status(), StatusCode::OK);
+    assert_eq!(
+        send(&first.key).await.unwrap().status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(send(&second.key).await.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_key_roles() {
+    let (app, pool) = setup_test_app_with(|config| config.auth_enabled = true).await;
+
+    sqlx::query(
+        "INSERT INTO countries (name, region, population, currency_code, last_refreshed_at)
+         VALUES ('Ghana', 'Africa', 31072940, 'GHS', NOW())",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let api_keys = ApiKeyRepository::new(pool.clone());
+    let reader = generate_api_key();
+    api_keys
+        .create("dashboard", Role::Reader, &reader.prefix, &reader.hash)
+        .await
+        .unwrap();
+    let admin = generate_api_key();
+    api_keys
+        .create("ops", Role::Admin, &admin.prefix, &admin.hash)
+        .await
+        .unwrap();
+
+    let send = |method: &str, uri: &str, key: Option<&str>| {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(key) = key {
+            request = request.header("x-api-key", key);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    let response = send("GET", "/countries/ghana", None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send("GET", "/countries/ghana", Some("cxa_wrong"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send("GET", "/countries/ghana", Some(&reader.key))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Another request within the minute leaves last_used_at alone
+    let last_used_at = || {
+        sqlx::query_as::<_, (Option<chrono::DateTime<chrono::Utc>>,)>(
+            "SELECT last_used_at FROM api_keys WHERE name = 'dashboard'",
+        )
+        .fetch_one(&pool)
+    };
+    let (first_used_at,) = last_used_at().await.unwrap();
+    assert!(first_used_at.is_some());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let response = send("GET", "/countries/ghana", Some(&reader.key))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(last_used_at().await.unwrap().0, first_used_at);
+
+    let response = send("DELETE", "/countries/ghana", Some(&reader.key))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send("DELETE", "/countries/ghana", Some(&admin.key))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let (actor,) = sqlx::query_as::<_, (Option<String>,)>(
+        "SELECT actor FROM audit_events WHERE country_name = 'Ghana'",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(actor.as_deref(), Some("ops"));
+}